@@ -6,6 +6,11 @@ pub mod lib {
                 pub mod effects {
                     pub mod playback;
                     pub mod transitions;
+                    pub mod beat_sync;
+                    pub mod progress_bar;
+                    pub mod scrolling_text;
+                    pub mod palette_strip;
+                    pub mod spatial_transitions;
                     pub mod waveforms {
                         pub mod waveform;
                         pub mod waveform_impl;
@@ -13,6 +18,7 @@ pub mod lib {
                     pub mod base {
                         pub mod math;
                         pub mod overlay;
+                        pub mod font;
                         pub mod effect;
                     }
             }
@@ -28,16 +34,35 @@ pub mod lib {
     pub mod models {
         pub mod animation;
         pub mod frame;
+        pub mod frame_clock;
+        pub mod frame_source;
+        pub mod streamed_animation;
+        pub mod transition;
+        pub mod pixel_map;
+        pub mod channel_encoder;
+        pub mod color_correction;
         pub mod playback_state;
         pub mod app_channels;
     }
     pub mod web {
         pub mod webserver;
     }
+    pub mod protocols {
+        pub mod protocol;
+        pub mod universe;
+        pub mod artnet;
+        pub mod sacn;
+        pub mod ddp;
+        pub mod wled;
+        pub mod discovery;
+    }
 }
 
 pub mod utils {
     pub mod image;
     pub mod spotify;
     pub mod network;
+    pub mod wled;
+    pub mod palette;
+    pub mod color;
 }
@@ -1,3 +1,4 @@
+use std::sync::Arc;
 use crate::lib::{artnet::anim::effects::waveforms::waveform::{WaveformEffect, WaveformParameters}, models::frame::AnimationFrame};
 use crate::settings::SETTINGS;
 
@@ -11,8 +12,7 @@ impl RenderedEffect {
         let mut result = vec![];
 
         for i in 0..self.multipliers.len() {
-            let frame = image.iter().map(|x| (*x as f64 * self.multipliers[i]) as u8).collect();
-            result.push(AnimationFrame::new(&frame));
+            result.push(apply_multiplier(image, self.multipliers[i]));
         }
 
         result
@@ -25,6 +25,43 @@ impl RenderedEffect {
     }
 }
 
+/// Brightness varying effect that computes its multiplier on demand, for any point in time
+///
+/// Unlike `RenderedEffect`, nothing is precomputed, so it can be cloned and played for any length of time.
+#[derive(Clone)]
+pub struct StreamedEffect {
+    elements: Arc<Vec<WaveformEffectElement>>,
+}
+
+impl StreamedEffect {
+    ///
+    /// Returns the brightness multiplier at `time` seconds since the start of the effect.
+    /// Elements are played one after the other, and the effect loops after the last element.
+    pub fn multiplier_at(&self, time: f64) -> f64 {
+        let duration: f64 = self.elements.iter().map(|element| element.duration()).sum();
+
+        if duration <= 0.0 {
+            return 1.0;
+        }
+
+        let mut element_time = time % duration;
+
+        for element in self.elements.iter() {
+            if element_time < element.duration() {
+                return element.effect.math_func(element_time, element.parameters);
+            }
+            element_time -= element.duration();
+        }
+
+        1.0
+    }
+}
+
+/// Applies a brightness multiplier to every value of the image
+pub fn apply_multiplier(image: &[u8], multiplier: f64) -> AnimationFrame {
+    AnimationFrame { data: image.iter().map(|x| (*x as f64 * multiplier) as u8).collect() }
+}
+
 pub struct WaveformEffectElement {
     effect: Box<dyn WaveformEffect>,
     parameters: WaveformParameters,
@@ -35,6 +72,13 @@ pub struct WaveformEffectElement {
 }
 
 
+impl WaveformEffectElement {
+    /// Duration of the element in seconds
+    fn duration(&self) -> f64 {
+        self.parameters.period * self.slice_factor
+    }
+}
+
 /// EffectBuilder
 /// 
 /// TODO: add docs here
@@ -49,8 +93,7 @@ impl EffectBuilder {
 
     // TODO: currently, this is only for waveform brightness effects
     // this will add effect one after the other, they don't mix
-    // i'll have to implement overlay, etc. in the future
-    // which will add effects on top of each other
+    // to mix effects, add them as layers of an `Overlay` instead
     pub fn add_brightness_effect(&mut self, effect: impl WaveformEffect + 'static, parameters: WaveformParameters, slice_factor: f64) {
         self.elements.push(WaveformEffectElement { effect: Box::new(effect), parameters, slice_factor });
     }
@@ -67,4 +110,9 @@ impl EffectBuilder {
 
         RenderedEffect { multipliers: result }
     }
+
+    /// Builds an effect that computes its multipliers on demand, see `StreamedEffect`
+    pub fn build_streamed(self) -> StreamedEffect {
+        StreamedEffect { elements: Arc::new(self.elements) }
+    }
 }
\ No newline at end of file
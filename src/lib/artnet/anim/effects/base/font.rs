@@ -0,0 +1,234 @@
+// bitmap font for rendering text on matrices
+
+// width of a glyph, and the blank column between glyphs
+pub static GLYPH_WIDTH: usize = 5;
+static GLYPH_SPACING: usize = 1;
+// rows of a glyph (7), and the two rows above it for accents
+pub static GLYPH_HEIGHT: usize = 9;
+static ACCENT_ROWS: usize = 2;
+
+/// Classic 5x7 font for printable ASCII (0x20 - 0x7E)
+///
+/// Each glyph is 5 columns from left to right, the lowest bit of each column being its top row.
+static ASCII_GLYPHS: [[u8; 5]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00], // ' '
+    [0x00, 0x00, 0x5f, 0x00, 0x00], // !
+    [0x00, 0x07, 0x00, 0x07, 0x00], // "
+    [0x14, 0x7f, 0x14, 0x7f, 0x14], // #
+    [0x24, 0x2a, 0x7f, 0x2a, 0x12], // $
+    [0x23, 0x13, 0x08, 0x64, 0x62], // %
+    [0x36, 0x49, 0x55, 0x22, 0x50], // &
+    [0x00, 0x05, 0x03, 0x00, 0x00], // '
+    [0x00, 0x1c, 0x22, 0x41, 0x00], // (
+    [0x00, 0x41, 0x22, 0x1c, 0x00], // )
+    [0x14, 0x08, 0x3e, 0x08, 0x14], // *
+    [0x08, 0x08, 0x3e, 0x08, 0x08], // +
+    [0x00, 0x50, 0x30, 0x00, 0x00], // ,
+    [0x08, 0x08, 0x08, 0x08, 0x08], // -
+    [0x00, 0x60, 0x60, 0x00, 0x00], // .
+    [0x20, 0x10, 0x08, 0x04, 0x02], // /
+    [0x3e, 0x51, 0x49, 0x45, 0x3e], // 0
+    [0x00, 0x42, 0x7f, 0x40, 0x00], // 1
+    [0x42, 0x61, 0x51, 0x49, 0x46], // 2
+    [0x21, 0x41, 0x45, 0x4b, 0x31], // 3
+    [0x18, 0x14, 0x12, 0x7f, 0x10], // 4
+    [0x27, 0x45, 0x45, 0x45, 0x39], // 5
+    [0x3c, 0x4a, 0x49, 0x49, 0x30], // 6
+    [0x01, 0x71, 0x09, 0x05, 0x03], // 7
+    [0x36, 0x49, 0x49, 0x49, 0x36], // 8
+    [0x06, 0x49, 0x49, 0x29, 0x1e], // 9
+    [0x00, 0x36, 0x36, 0x00, 0x00], // :
+    [0x00, 0x56, 0x36, 0x00, 0x00], // ;
+    [0x08, 0x14, 0x22, 0x41, 0x00], // <
+    [0x14, 0x14, 0x14, 0x14, 0x14], // =
+    [0x00, 0x41, 0x22, 0x14, 0x08], // >
+    [0x02, 0x01, 0x51, 0x09, 0x06], // ?
+    [0x32, 0x49, 0x79, 0x41, 0x3e], // @
+    [0x7e, 0x11, 0x11, 0x11, 0x7e], // A
+    [0x7f, 0x49, 0x49, 0x49, 0x36], // B
+    [0x3e, 0x41, 0x41, 0x41, 0x22], // C
+    [0x7f, 0x41, 0x41, 0x22, 0x1c], // D
+    [0x7f, 0x49, 0x49, 0x49, 0x41], // E
+    [0x7f, 0x09, 0x09, 0x01, 0x01], // F
+    [0x3e, 0x41, 0x41, 0x51, 0x32], // G
+    [0x7f, 0x08, 0x08, 0x08, 0x7f], // H
+    [0x00, 0x41, 0x7f, 0x41, 0x00], // I
+    [0x20, 0x40, 0x41, 0x3f, 0x01], // J
+    [0x7f, 0x08, 0x14, 0x22, 0x41], // K
+    [0x7f, 0x40, 0x40, 0x40, 0x40], // L
+    [0x7f, 0x02, 0x04, 0x02, 0x7f], // M
+    [0x7f, 0x04, 0x08, 0x10, 0x7f], // N
+    [0x3e, 0x41, 0x41, 0x41, 0x3e], // O
+    [0x7f, 0x09, 0x09, 0x09, 0x06], // P
+    [0x3e, 0x41, 0x51, 0x21, 0x5e], // Q
+    [0x7f, 0x09, 0x19, 0x29, 0x46], // R
+    [0x46, 0x49, 0x49, 0x49, 0x31], // S
+    [0x01, 0x01, 0x7f, 0x01, 0x01], // T
+    [0x3f, 0x40, 0x40, 0x40, 0x3f], // U
+    [0x1f, 0x20, 0x40, 0x20, 0x1f], // V
+    [0x7f, 0x20, 0x18, 0x20, 0x7f], // W
+    [0x63, 0x14, 0x08, 0x14, 0x63], // X
+    [0x03, 0x04, 0x78, 0x04, 0x03], // Y
+    [0x61, 0x51, 0x49, 0x45, 0x43], // Z
+    [0x00, 0x7f, 0x41, 0x41, 0x00], // [
+    [0x02, 0x04, 0x08, 0x10, 0x20], // \
+    [0x00, 0x41, 0x41, 0x7f, 0x00], // ]
+    [0x04, 0x02, 0x01, 0x02, 0x04], // ^
+    [0x40, 0x40, 0x40, 0x40, 0x40], // _
+    [0x00, 0x01, 0x02, 0x04, 0x00], // `
+    [0x20, 0x54, 0x54, 0x54, 0x78], // a
+    [0x7f, 0x48, 0x44, 0x44, 0x38], // b
+    [0x38, 0x44, 0x44, 0x44, 0x20], // c
+    [0x38, 0x44, 0x44, 0x48, 0x7f], // d
+    [0x38, 0x54, 0x54, 0x54, 0x18], // e
+    [0x08, 0x7e, 0x09, 0x01, 0x02], // f
+    [0x08, 0x14, 0x54, 0x54, 0x3c], // g
+    [0x7f, 0x08, 0x04, 0x04, 0x78], // h
+    [0x00, 0x44, 0x7d, 0x40, 0x00], // i
+    [0x20, 0x40, 0x44, 0x3d, 0x00], // j
+    [0x00, 0x7f, 0x10, 0x28, 0x44], // k
+    [0x00, 0x41, 0x7f, 0x40, 0x00], // l
+    [0x7c, 0x04, 0x18, 0x04, 0x78], // m
+    [0x7c, 0x08, 0x04, 0x04, 0x78], // n
+    [0x38, 0x44, 0x44, 0x44, 0x38], // o
+    [0x7c, 0x14, 0x14, 0x14, 0x08], // p
+    [0x08, 0x14, 0x14, 0x18, 0x7c], // q
+    [0x7c, 0x08, 0x04, 0x04, 0x08], // r
+    [0x48, 0x54, 0x54, 0x54, 0x20], // s
+    [0x04, 0x3f, 0x44, 0x40, 0x20], // t
+    [0x3c, 0x40, 0x40, 0x20, 0x7c], // u
+    [0x1c, 0x20, 0x40, 0x20, 0x1c], // v
+    [0x3c, 0x40, 0x30, 0x40, 0x3c], // w
+    [0x44, 0x28, 0x10, 0x28, 0x44], // x
+    [0x0c, 0x50, 0x50, 0x50, 0x3c], // y
+    [0x44, 0x64, 0x54, 0x4c, 0x44], // z
+    [0x00, 0x08, 0x36, 0x41, 0x00], // {
+    [0x00, 0x00, 0x7f, 0x00, 0x00], // |
+    [0x00, 0x41, 0x36, 0x08, 0x00], // }
+    [0x02, 0x01, 0x02, 0x04, 0x02], // ~
+];
+
+// `i` and `j` without their dot, for accented variants
+static DOTLESS_I: [u8; 5] = [0x00, 0x44, 0x7c, 0x40, 0x00];
+static DOTLESS_J: [u8; 5] = [0x20, 0x40, 0x44, 0x3c, 0x00];
+
+/// Accent drawn in the two rows above a glyph
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Accent {
+    Grave,
+    Acute,
+    Circumflex,
+    Tilde,
+    Diaeresis,
+    Ring,
+    Caron,
+}
+
+impl Accent {
+    /// Columns lit in each of the two accent rows, the lowest bit being the leftmost column
+    fn rows(&self) -> [u8; 2] {
+        match self {
+            Accent::Grave => [0b00010, 0b00100],
+            Accent::Acute => [0b01000, 0b00100],
+            Accent::Circumflex => [0b00100, 0b01010],
+            Accent::Tilde => [0b10110, 0b01001],
+            Accent::Diaeresis => [0b00000, 0b01010],
+            Accent::Ring => [0b01110, 0b01010],
+            Accent::Caron => [0b01010, 0b00100],
+        }
+    }
+}
+
+/// Accented (or otherwise decorated) characters, with their base characters at the same positions
+static DECOMPOSITIONS: [(&str, &str, Option<Accent>); 8] = [
+    ("ÀÈÌÒÙàèìòù", "AEIOUaeiou", Some(Accent::Grave)),
+    ("ÁÉÍÓÚÝáéíóúýĆćŃńŚśŹź", "AEIOUYaeiouyCcNnSsZz", Some(Accent::Acute)),
+    ("ÂÊÎÔÛâêîôû", "AEIOUaeiou", Some(Accent::Circumflex)),
+    ("ÃÑÕãñõ", "ANOano", Some(Accent::Tilde)),
+    ("ÄËÏÖÜŸäëïöüÿ", "AEIOUYaeiouy", Some(Accent::Diaeresis)),
+    ("Åå", "Aa", Some(Accent::Ring)),
+    ("ČčŠšŽžŘřĚě", "CcSsZzRrEe", Some(Accent::Caron)),
+    // no room for marks below or through the glyph, so these are drawn as their base character
+    ("ÇçØøŁłĘęĄąÐð", "CcOoLlEeAaDd", None),
+];
+
+/// Characters drawn as multiple glyphs, or as a similar ASCII character
+static REPLACEMENTS: [(char, &str); 10] = [
+    ('Æ', "AE"), ('æ', "ae"), ('Œ', "OE"), ('œ', "oe"), ('ß', "ss"),
+    ('–', "-"), ('—', "-"), ('‘', "'"), ('’', "'"), ('…', "..."),
+];
+
+/// Monochrome bitmap of rendered text
+///
+/// `width` - width in pixels
+/// `height` - height in pixels, always `GLYPH_HEIGHT`
+/// `pixels` - whether each pixel is lit, row by row
+pub struct TextBitmap {
+    pub width: usize,
+    pub height: usize,
+    pixels: Vec<bool>,
+}
+
+impl TextBitmap {
+    pub fn is_lit(&self, x: usize, y: usize) -> bool {
+        x < self.width && y < self.height && self.pixels[y * self.width + x]
+    }
+}
+
+/// Built-in bitmap font, 5x7 glyphs for ASCII and the common accented Latin characters
+///
+/// Unknown characters are drawn as `?`.
+pub struct BitmapFont;
+
+impl BitmapFont {
+    ///
+    /// Renders `text` in a single line
+    pub fn render(text: &str) -> TextBitmap {
+        let glyphs: Vec<([u8; 5], Option<Accent>)> = text.chars()
+            .flat_map(|c| match REPLACEMENTS.iter().find(|(replaced, _)| *replaced == c) {
+                Some((_, replacement)) => replacement.chars().collect::<Vec<char>>(),
+                None => vec![c],
+            })
+            .map(BitmapFont::glyph)
+            .collect();
+
+        let width = (glyphs.len() * (GLYPH_WIDTH + GLYPH_SPACING)).saturating_sub(GLYPH_SPACING);
+        let mut pixels = vec![false; width * GLYPH_HEIGHT];
+
+        for (i, (columns, accent)) in glyphs.iter().enumerate() {
+            let left = i * (GLYPH_WIDTH + GLYPH_SPACING);
+
+            for (x, column) in columns.iter().enumerate() {
+                for y in 0..(GLYPH_HEIGHT - ACCENT_ROWS) {
+                    pixels[(y + ACCENT_ROWS) * width + left + x] = column & (1 << y) != 0;
+                }
+            }
+
+            if let Some(accent) = accent {
+                for (y, row) in accent.rows().iter().enumerate() {
+                    for x in 0..GLYPH_WIDTH {
+                        pixels[y * width + left + x] = row & (1 << x) != 0;
+                    }
+                }
+            }
+        }
+
+        TextBitmap { width, height: GLYPH_HEIGHT, pixels }
+    }
+
+    /// Glyph and accent of a single character
+    fn glyph(c: char) -> ([u8; 5], Option<Accent>) {
+        let (base, accent) = DECOMPOSITIONS.iter()
+            .find_map(|(accented, bases, accent)| {
+                accented.chars().position(|a| a == c).map(|i| (bases.chars().nth(i).unwrap(), *accent))
+            })
+            .unwrap_or((c, None));
+
+        match (base, accent) {
+            ('i', Some(_)) => (DOTLESS_I, accent),
+            ('j', Some(_)) => (DOTLESS_J, accent),
+            (' '..='~', _) => (ASCII_GLYPHS[base as usize - ' ' as usize], accent),
+            _ => (ASCII_GLYPHS['?' as usize - ' ' as usize], None),
+        }
+    }
+}
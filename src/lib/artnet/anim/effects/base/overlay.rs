@@ -1,8 +1,271 @@
 // overlay effects, that are applied on top of the current frame
-pub struct Overlay {
+use std::sync::Arc;
+use crate::lib::models::frame::{AnimationFrame, VALUES_PER_PIXEL};
+use super::effect::StreamedEffect;
+
+/// RGBA pixel, each value 0.0 - 1.0
+pub type Rgba = [f64; 4];
+
+/// How a layer is combined with the layers below it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BlendMode {
+    // darkens, e.g. for brightness effects
+    Multiply,
+    // brightens, clipped at full brightness
+    Add,
+    // brightens without clipping, the inverse of multiply
+    Screen,
+    // replaces the layers below, according to the layer's alpha
+    AlphaOver,
+    // keeps the brighter of the two
+    Max,
+}
+
+impl BlendMode {
+    fn blend(&self, base: f64, layer: f64) -> f64 {
+        match self {
+            BlendMode::Multiply => base * layer,
+            BlendMode::Add => (base + layer).min(1.0),
+            BlendMode::Screen => 1.0 - (1.0 - base) * (1.0 - layer),
+            BlendMode::AlphaOver => layer,
+            BlendMode::Max => base.max(layer),
+        }
+    }
+}
+
+/// Content of a layer, rendered on demand for any point in time
+pub trait LayerSource: Send + Sync {
+    ///
+    /// Renders `num_pixels` pixels at `time` seconds since the start of the animation
+    fn render(&self, time: f64, num_pixels: usize) -> Vec<Rgba>;
+}
+
+/// Shared layers, e.g. the same effect played on all targets
+impl<T: LayerSource + ?Sized> LayerSource for Arc<T> {
+    fn render(&self, time: f64, num_pixels: usize) -> Vec<Rgba> {
+        self.as_ref().render(time, num_pixels)
+    }
+}
+
+/// Fully opaque, static image layer
+pub struct ImageLayer {
+    image: Vec<u8>,
+}
+
+impl ImageLayer {
+    pub fn new(image: Vec<u8>) -> Self {
+        Self { image }
+    }
+}
 
+impl LayerSource for ImageLayer {
+    fn render(&self, _time: f64, num_pixels: usize) -> Vec<Rgba> {
+        let mut pixels: Vec<Rgba> = self.image.chunks_exact(VALUES_PER_PIXEL)
+            .map(|pixel| [pixel[0] as f64 / 255.0, pixel[1] as f64 / 255.0, pixel[2] as f64 / 255.0, 1.0])
+            .collect();
+
+        pixels.resize(num_pixels, [0.0, 0.0, 0.0, 1.0]);
+        pixels
+    }
+}
+
+/// Waveform layers are uniform grey at the brightness multiplier of the effect,
+/// so multiplying them onto a layer applies the effect to it
+impl LayerSource for StreamedEffect {
+    fn render(&self, time: f64, num_pixels: usize) -> Vec<Rgba> {
+        let multiplier = self.multiplier_at(time).clamp(0.0, 1.0);
+        vec![[multiplier, multiplier, multiplier, 1.0]; num_pixels]
+    }
+}
+
+/// A layer of an `Overlay`
+///
+/// `source` - content of the layer
+/// `blend` - how the layer is combined with the layers below it
+/// `opacity` - 0.0 - 1.0, multiplied with the alpha of each pixel of the layer
+pub struct OverlayLayer {
+    source: Box<dyn LayerSource>,
+    blend: BlendMode,
+    opacity: f64,
+}
+
+/// Layered compositor
+///
+/// Layers are composited bottom to top onto a transparent black canvas,
+/// so effects can be mixed (e.g. a beat pulse multiplied onto a slow breathing effect)
+/// instead of only being played one after the other as with `EffectBuilder`.
+///
+/// An Overlay is itself a `LayerSource`, so overlays can be nested as layer groups.
+#[derive(Default)]
+pub struct Overlay {
+    layers: Vec<OverlayLayer>,
 }
 
 impl Overlay {
+    pub fn new() -> Self {
+        Self { layers: Vec::new() }
+    }
+
+    /// Adds a layer on top of the existing layers
+    pub fn add_layer(&mut self, source: impl LayerSource + 'static, blend: BlendMode, opacity: f64) {
+        self.layers.push(OverlayLayer { source: Box::new(source), blend, opacity: opacity.clamp(0.0, 1.0) });
+    }
+
+    ///
+    /// Composites all layers at `time` seconds into an RGB frame of `num_pixels` pixels, on a black background
+    pub fn apply(&self, time: f64, num_pixels: usize) -> AnimationFrame {
+        AnimationFrame {
+            data: self.composite(time, num_pixels).iter()
+                .flat_map(|pixel| pixel[..VALUES_PER_PIXEL].iter().map(|value| (value.clamp(0.0, 1.0) * 255.0).round() as u8).collect::<Vec<u8>>())
+                .collect()
+        }
+    }
+
+    /// Composites all layers, the color of each pixel is as seen on a black background (i.e. premultiplied by its alpha)
+    fn composite(&self, time: f64, num_pixels: usize) -> Vec<Rgba> {
+        let mut canvas: Vec<Rgba> = vec![[0.0, 0.0, 0.0, 0.0]; num_pixels];
+
+        for layer in self.layers.iter() {
+            let pixels = layer.source.render(time, num_pixels);
+
+            for (base, pixel) in canvas.iter_mut().zip(pixels.iter()) {
+                let alpha = pixel[3] * layer.opacity;
+
+                for channel in 0..VALUES_PER_PIXEL {
+                    base[channel] += (layer.blend.blend(base[channel], pixel[channel]) - base[channel]) * alpha;
+                }
 
-}
\ No newline at end of file
+                // multiplying only darkens what's already there, other modes add coverage
+                if layer.blend != BlendMode::Multiply {
+                    base[3] = alpha + base[3] * (1.0 - alpha);
+                }
+            }
+        }
+
+        canvas
+    }
+}
+
+impl LayerSource for Overlay {
+    fn render(&self, time: f64, num_pixels: usize) -> Vec<Rgba> {
+        // layer colors are not premultiplied, so the alpha isn't applied twice when the group is blended
+        self.composite(time, num_pixels).into_iter()
+            .map(|[r, g, b, alpha]| match alpha > 0.0 {
+                true => [r / alpha, g / alpha, b / alpha, alpha],
+                false => [0.0, 0.0, 0.0, 0.0],
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Layer of a single color on every pixel
+    struct Solid(Rgba);
+
+    impl LayerSource for Solid {
+        fn render(&self, _time: f64, num_pixels: usize) -> Vec<Rgba> {
+            vec![self.0; num_pixels]
+        }
+    }
+
+    fn assert_pixel(actual: Rgba, expected: Rgba) {
+        assert!(actual.iter().zip(expected.iter()).all(|(a, e)| (a - e).abs() < 1e-9), "expected {:?}, got {:?}", expected, actual);
+    }
+
+    /// Composite of `layer` blended onto an opaque [0.5, 0.4, 0.2] base
+    fn on_base(layer: Rgba, blend: BlendMode, opacity: f64) -> Rgba {
+        let mut overlay = Overlay::new();
+        overlay.add_layer(Solid([0.5, 0.4, 0.2, 1.0]), BlendMode::AlphaOver, 1.0);
+        overlay.add_layer(Solid(layer), blend, opacity);
+
+        overlay.composite(0.0, 1)[0]
+    }
+
+    #[test]
+    fn multiplies() {
+        assert_pixel(on_base([0.5, 0.5, 1.0, 1.0], BlendMode::Multiply, 1.0), [0.25, 0.2, 0.2, 1.0]);
+    }
+
+    #[test]
+    fn adds_clipped() {
+        assert_pixel(on_base([0.6, 0.6, 0.6, 1.0], BlendMode::Add, 1.0), [1.0, 1.0, 0.8, 1.0]);
+    }
+
+    #[test]
+    fn screens() {
+        // 1 - (1 - base) * (1 - 0.5)
+        assert_pixel(on_base([0.5, 0.5, 0.5, 1.0], BlendMode::Screen, 1.0), [0.75, 0.7, 0.6, 1.0]);
+    }
+
+    #[test]
+    fn alpha_overs_by_layer_alpha() {
+        // halfway between the base and red
+        assert_pixel(on_base([1.0, 0.0, 0.0, 0.5], BlendMode::AlphaOver, 1.0), [0.75, 0.2, 0.1, 1.0]);
+    }
+
+    #[test]
+    fn keeps_max() {
+        assert_pixel(on_base([0.3, 0.9, 0.1, 1.0], BlendMode::Max, 1.0), [0.5, 0.9, 0.2, 1.0]);
+    }
+
+    #[test]
+    fn applies_layer_opacity() {
+        // halfway between the base and the added [1.0, 1.0, 0.8]
+        assert_pixel(on_base([0.6, 0.6, 0.6, 1.0], BlendMode::Add, 0.5), [0.75, 0.7, 0.5, 1.0]);
+        // opacity and alpha are multiplied
+        assert_pixel(on_base([1.0, 0.0, 0.0, 0.5], BlendMode::AlphaOver, 0.5), [0.625, 0.3, 0.15, 1.0]);
+        assert_pixel(on_base([1.0, 0.0, 0.0, 1.0], BlendMode::AlphaOver, 0.0), [0.5, 0.4, 0.2, 1.0]);
+    }
+
+    #[test]
+    fn multiply_adds_no_coverage() {
+        let mut overlay = Overlay::new();
+        overlay.add_layer(Solid([0.5, 0.5, 0.5, 1.0]), BlendMode::Multiply, 1.0);
+
+        assert_pixel(overlay.composite(0.0, 1)[0], [0.0, 0.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn composites_premultiplied_and_renders_unpremultiplied() {
+        let mut group = Overlay::new();
+        group.add_layer(Solid([1.0, 0.5, 0.0, 0.5]), BlendMode::AlphaOver, 1.0);
+
+        // as seen on black, then the layer's own color
+        assert_pixel(group.composite(0.0, 1)[0], [0.5, 0.25, 0.0, 0.5]);
+        assert_pixel(group.render(0.0, 1)[0], [1.0, 0.5, 0.0, 0.5]);
+    }
+
+    #[test]
+    fn nested_group_matches_flat_layers() {
+        let mut group = Overlay::new();
+        group.add_layer(Solid([1.0, 0.5, 0.0, 0.5]), BlendMode::AlphaOver, 1.0);
+        let mut nested = Overlay::new();
+        nested.add_layer(Solid([0.0, 0.0, 1.0, 1.0]), BlendMode::AlphaOver, 1.0);
+        nested.add_layer(group, BlendMode::AlphaOver, 1.0);
+
+        let mut flat = Overlay::new();
+        flat.add_layer(Solid([0.0, 0.0, 1.0, 1.0]), BlendMode::AlphaOver, 1.0);
+        flat.add_layer(Solid([1.0, 0.5, 0.0, 0.5]), BlendMode::AlphaOver, 1.0);
+
+        // the group's alpha is only applied once
+        assert_pixel(nested.composite(0.0, 1)[0], [0.5, 0.25, 0.5, 1.0]);
+        assert_pixel(nested.composite(0.0, 1)[0], flat.composite(0.0, 1)[0]);
+    }
+
+    #[test]
+    fn fully_transparent_group_renders_transparent() {
+        assert_pixel(Overlay::new().render(0.0, 1)[0], [0.0, 0.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn applies_to_rgb_frame_on_black() {
+        let mut overlay = Overlay::new();
+        overlay.add_layer(Solid([1.0, 0.5, 0.0, 0.5]), BlendMode::AlphaOver, 1.0);
+
+        // 0.5 * 255 = 127.5 and 0.25 * 255 = 63.75, rounded
+        assert_eq!(overlay.apply(0.0, 2).data, [128, 64, 0, 128, 64, 0]);
+    }
+}
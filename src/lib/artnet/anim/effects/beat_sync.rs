@@ -0,0 +1,374 @@
+use std::f64::consts::PI;
+use std::sync::Arc;
+use std::time::Instant;
+use log::debug;
+use rspotify::model::{AudioAnalysis, TimeInterval};
+use super::base::effect::StreamedEffect;
+use super::base::overlay::{LayerSource, Rgba};
+
+// brightness dip between beats at full loudness, 0.0 - 1.0
+static MAX_PULSE_DEPTH: f64 = 0.5;
+// segment loudness (dB) at or below which there is no pulse
+static MIN_LOUDNESS: f64 = -40.0;
+// section loudness relative to the track's (dB), at or below which the section is calm, and at or above which it is intense
+static CALM_LOUDNESS_DIFF: f64 = -4.0;
+static INTENSE_LOUDNESS_DIFF: f64 = 1.5;
+// without an audio analysis: length of the intro and outro (seconds), assumed to be calm
+static ESTIMATED_INTRO_SECONDS: f64 = 15.0;
+static ESTIMATED_OUTRO_SECONDS: f64 = 20.0;
+
+/// Style of the effect during a section of the track, from the section's loudness relative to the whole track
+///
+/// With an audio analysis, the style follows its sections (see `BeatSyncEffect`);
+/// with only the audio features, it is estimated from the position in the track (see `ScheduledFeaturesEffect`).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SectionStyle {
+    // e.g. intros and breakdowns: slow breathing over each bar instead of pulsing on the beats
+    Calm,
+    Normal,
+    // e.g. choruses: deeper and sharper pulses
+    Intense,
+}
+
+impl SectionStyle {
+    /// Fraction of the pulse depth used in the section
+    fn depth_factor(&self) -> f64 {
+        match self {
+            SectionStyle::Calm => 0.5,
+            SectionStyle::Normal => 0.75,
+            SectionStyle::Intense => 1.0,
+        }
+    }
+
+    /// Factor of the pulse exponent used in the section
+    fn exponent_factor(&self) -> f64 {
+        match self {
+            SectionStyle::Intense => 1.5,
+            _ => 1.0,
+        }
+    }
+
+    ///
+    /// Estimates the style at `position` seconds into a track of `duration` seconds, without an audio analysis:
+    /// calm during the intro and outro, normal otherwise (choruses can't be told apart without the analysis).
+    pub fn estimate(position: f64, duration: Option<f64>) -> SectionStyle {
+        let outro = duration.is_some_and(|duration| duration > ESTIMATED_INTRO_SECONDS + ESTIMATED_OUTRO_SECONDS && position >= duration - ESTIMATED_OUTRO_SECONDS);
+
+        match position < ESTIMATED_INTRO_SECONDS || outro {
+            true => SectionStyle::Calm,
+            false => SectionStyle::Normal,
+        }
+    }
+}
+
+/// Brightness effect pulsing on the beats of the track's audio analysis
+///
+/// Unlike `PlaybackEffects::play_features`, pulses are aligned to the actual beat timestamps,
+/// so they don't drift off the music over the course of the track.
+/// The position in the track is taken from the wall clock, starting from the position interpolated by `PlaybackState`.
+/// The style of the effect changes at section boundaries, see `SectionStyle`.
+///
+/// `analysis` - audio analysis of the track
+/// `styles` - style of each section of `analysis`
+/// `progress` - position in the track in seconds when it was observed
+/// `observed_at` - when `progress` was observed
+/// `exponent` - sharpness of the pulses, higher decays faster after each beat
+pub struct BeatSyncEffect {
+    analysis: Arc<AudioAnalysis>,
+    styles: Vec<SectionStyle>,
+    progress: f64,
+    observed_at: Instant,
+    exponent: f64,
+}
+
+impl BeatSyncEffect {
+    pub fn new(analysis: Arc<AudioAnalysis>, progress: f64, observed_at: Instant, exponent: f64) -> Self {
+        let track_loudness = analysis.track.loudness as f64;
+
+        let styles: Vec<SectionStyle> = analysis.sections.iter().map(|section| {
+            match section.loudness as f64 - track_loudness {
+                diff if diff <= CALM_LOUDNESS_DIFF => SectionStyle::Calm,
+                diff if diff >= INTENSE_LOUDNESS_DIFF => SectionStyle::Intense,
+                _ => SectionStyle::Normal,
+            }
+        }).collect();
+        debug!("Section styles: {:?}", styles);
+
+        Self { analysis, styles, progress, observed_at, exponent: exponent.max(1.0) }
+    }
+
+    /// Position in the track in seconds, right now
+    pub fn position(&self) -> f64 {
+        self.progress + self.observed_at.elapsed().as_secs_f64()
+    }
+
+    ///
+    /// Returns the brightness multiplier at `position` seconds into the track:
+    /// full brightness on each beat, decaying until the next one (or breathing over each bar in calm sections),
+    /// dipping further the louder the current segment is.
+    pub fn multiplier_at(&self, position: f64) -> f64 {
+        let style = self.style_at(position);
+        let depth = MAX_PULSE_DEPTH * style.depth_factor() * self.loudness_at(position);
+
+        let pulse = match style {
+            // smooth cosine, bright at the start and end of each bar
+            SectionStyle::Calm => phase_at(&self.analysis.bars, position).map_or(0.0, |phase| (1.0 + (2.0 * PI * phase).cos()) / 2.0),
+            _ => phase_at(&self.analysis.beats, position).map_or(0.0, |phase| (1.0 - phase).powf(self.exponent * style.exponent_factor())),
+        };
+
+        1.0 - depth * (1.0 - pulse)
+    }
+
+    /// Style of the section at `position` seconds, `Normal` outside of the sections
+    pub fn style_at(&self, position: f64) -> SectionStyle {
+        let section = self.analysis.sections.partition_point(|section| (section.time_interval.start as f64) <= position).checked_sub(1);
+        section.map_or(SectionStyle::Normal, |i| self.styles[i])
+    }
+
+    /// Loudness of the segment at `position` seconds, 0.0 (at or below `MIN_LOUDNESS`) - 1.0 (0 dB)
+    fn loudness_at(&self, position: f64) -> f64 {
+        let segment = self.analysis.segments.partition_point(|segment| (segment.time_interval.start as f64) <= position).checked_sub(1);
+
+        match segment.map(|i| &self.analysis.segments[i]) {
+            Some(segment) => (1.0 - segment.loudness_max as f64 / MIN_LOUDNESS).clamp(0.0, 1.0),
+            None => 0.0,
+        }
+    }
+}
+
+impl LayerSource for BeatSyncEffect {
+    fn render(&self, _time: f64, num_pixels: usize) -> Vec<Rgba> {
+        let multiplier = self.multiplier_at(self.position());
+        vec![[multiplier, multiplier, multiplier, 1.0]; num_pixels]
+    }
+}
+
+/// Tempo based effect for tracks without an audio analysis, switching between a calm and a normal effect
+/// at the estimated section boundaries, see `SectionStyle::estimate`
+///
+/// `calm`, `normal` - effects played in calm and other sections
+/// `progress` - position in the track in seconds at the start of the animation
+/// `duration` - duration of the track in seconds, if known
+pub struct ScheduledFeaturesEffect {
+    calm: StreamedEffect,
+    normal: StreamedEffect,
+    progress: f64,
+    duration: Option<f64>,
+}
+
+impl ScheduledFeaturesEffect {
+    pub fn new(calm: StreamedEffect, normal: StreamedEffect, progress: f64, duration: Option<f64>) -> Self {
+        Self { calm, normal, progress, duration }
+    }
+
+    /// Style of the effect at `time` seconds since the start of the animation
+    pub fn style_at(&self, time: f64) -> SectionStyle {
+        SectionStyle::estimate(self.progress + time, self.duration)
+    }
+}
+
+impl LayerSource for ScheduledFeaturesEffect {
+    fn render(&self, time: f64, num_pixels: usize) -> Vec<Rgba> {
+        match self.style_at(time) {
+            SectionStyle::Calm => self.calm.render(time, num_pixels),
+            _ => self.normal.render(time, num_pixels),
+        }
+    }
+}
+
+///
+/// Returns how far (0.0 - 1.0) `position` is into the interval playing at `position`,
+/// or None before the first or after the last interval
+fn phase_at(intervals: &[TimeInterval], position: f64) -> Option<f64> {
+    let interval = intervals.partition_point(|interval| (interval.start as f64) <= position).checked_sub(1);
+
+    match interval.map(|i| &intervals[i]) {
+        Some(interval) if position < (interval.start + interval.duration) as f64 => {
+            Some((position - interval.start as f64) / interval.duration.max(f32::EPSILON) as f64)
+        },
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+    use rspotify::{ClientCredsSpotify, Credentials};
+    use rspotify::clients::BaseClient;
+    use rspotify::model::TrackId;
+    use super::*;
+
+    // recorded fixtures are trimmed to the start of the track, to keep them small
+    static RECORDED_SECONDS: f32 = 60.0;
+
+    fn fixtures_dir() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("fixtures")
+    }
+
+    fn load(name: &str) -> AudioAnalysis {
+        serde_json::from_str(&fs::read_to_string(fixtures_dir().join(name)).unwrap()).unwrap()
+    }
+
+    ///
+    /// Every audio analysis fixture: the synthetic one, and any recorded with `record_audio_analysis`
+    fn fixtures() -> Vec<(String, BeatSyncEffect)> {
+        let mut names: Vec<String> = fs::read_dir(fixtures_dir()).unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .filter(|name| name.starts_with("audio_analysis") && name.ends_with(".json"))
+            .collect();
+        names.sort();
+
+        names.into_iter().map(|name| {
+            let effect = BeatSyncEffect::new(Arc::new(load(&name)), 0.0, Instant::now(), 2.0);
+            (name, effect)
+        }).collect()
+    }
+
+    // 0.25 - 11.77 s at about 120 BPM, with uneven beat durations (0.47 - 0.53 s) and some low confidence beats,
+    // bars of 4 beats, sections 0 - 4.1 s (calm), 4.1 - 8.3 s (intense) and 8.3 - 12 s (normal) not starting on bars,
+    // and uneven segments at -20 dB until 8 s, then -40 dB
+    fn synthetic() -> BeatSyncEffect {
+        BeatSyncEffect::new(Arc::new(load("audio_analysis_synthetic.json")), 0.0, Instant::now(), 2.0)
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-6, "expected {}, got {}", expected, actual);
+    }
+
+    #[test]
+    fn styles_sections_by_loudness() {
+        let effect = synthetic();
+
+        assert_eq!(effect.style_at(2.0), SectionStyle::Calm);
+        // the bar starting at 2.25 s is still playing, but the section changes at 4.1 s
+        assert_eq!(effect.style_at(4.0), SectionStyle::Calm);
+        assert_eq!(effect.style_at(4.1), SectionStyle::Intense);
+        assert_eq!(effect.style_at(8.29), SectionStyle::Intense);
+        assert_eq!(effect.style_at(8.31), SectionStyle::Normal);
+        assert_eq!(effect.style_at(-1.0), SectionStyle::Normal);
+    }
+
+    #[test]
+    fn finds_beat_phase() {
+        let effect = synthetic();
+        let beats = &effect.analysis.beats;
+
+        assert_eq!(phase_at(beats, 0.1), None);
+        assert_eq!(phase_at(beats, 0.25), Some(0.0));
+        // the 0.47 s beat starting at 1.27 s
+        assert_close(phase_at(beats, 1.27 + 0.47 / 2.0).unwrap(), 0.5);
+        assert_close(phase_at(beats, 1.27 + 0.47 * 0.9).unwrap(), 0.9);
+        // after the last beat, ending at 11.77 s
+        assert_eq!(phase_at(beats, 11.9), None);
+    }
+
+    #[test]
+    fn pulses_on_beats_in_intense_sections() {
+        let effect = synthetic();
+
+        // full brightness on the 0.52 s beat at 4.25 s, then (1 - phase)^(2 * 1.5) of a 0.5 * 1.0 * 0.5 deep dip
+        assert_close(effect.multiplier_at(4.25), 1.0);
+        assert_close(effect.multiplier_at(4.25 + 0.26), 1.0 - 0.25 * (1.0 - 0.5f64.powi(3)));
+    }
+
+    #[test]
+    fn breathes_over_bars_in_calm_sections() {
+        let effect = synthetic();
+
+        // bright at the start of the 2 s bar at 2.25 s, darkest halfway through it, with a 0.5 * 0.5 * 0.5 deep dip
+        assert_close(effect.multiplier_at(2.25), 1.0);
+        assert_close(effect.multiplier_at(3.25), 1.0 - 0.125);
+    }
+
+    #[test]
+    fn follows_reported_progress() {
+        let effect = BeatSyncEffect::new(Arc::new(load("audio_analysis_synthetic.json")), 5.125, Instant::now(), 2.0);
+
+        let position = effect.position();
+        assert!((5.125..5.2).contains(&position));
+        assert_eq!(effect.style_at(position), SectionStyle::Intense);
+    }
+
+    #[test]
+    fn estimates_calm_intro_and_outro() {
+        assert_eq!(SectionStyle::estimate(0.0, Some(180.0)), SectionStyle::Calm);
+        assert_eq!(SectionStyle::estimate(15.0, Some(180.0)), SectionStyle::Normal);
+        assert_eq!(SectionStyle::estimate(159.0, Some(180.0)), SectionStyle::Normal);
+        assert_eq!(SectionStyle::estimate(160.0, Some(180.0)), SectionStyle::Calm);
+        assert_eq!(SectionStyle::estimate(160.0, None), SectionStyle::Normal);
+        // too short for both an intro and an outro
+        assert_eq!(SectionStyle::estimate(20.0, Some(30.0)), SectionStyle::Normal);
+    }
+
+    #[test]
+    fn switches_style_at_each_section_start() {
+        for (name, effect) in fixtures() {
+            for (i, section) in effect.analysis.sections.iter().enumerate() {
+                assert_eq!(effect.style_at(section.time_interval.start as f64), effect.styles[i], "{}: section {}", name, i);
+            }
+        }
+    }
+
+    #[test]
+    fn is_fully_bright_at_the_start_of_each_beat_or_bar() {
+        for (name, effect) in fixtures() {
+            let beats = effect.analysis.beats.iter().map(|beat| (beat, false));
+            let bars = effect.analysis.bars.iter().map(|bar| (bar, true));
+
+            // whatever their duration or confidence, calm sections breathe over bars, the others pulse on beats
+            for (interval, is_bar) in beats.chain(bars) {
+                let position = interval.start as f64;
+                if (effect.style_at(position) == SectionStyle::Calm) == is_bar {
+                    assert_eq!(effect.multiplier_at(position), 1.0, "{}: not fully bright at {} s", name, position);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn stays_within_pulse_depth() {
+        for (name, effect) in fixtures() {
+            let end = effect.analysis.segments.last().map_or(0.0, |segment| (segment.time_interval.start + segment.time_interval.duration) as f64);
+
+            for step in 0..(end * 100.0) as usize {
+                let multiplier = effect.multiplier_at(step as f64 / 100.0);
+                assert!((1.0 - MAX_PULSE_DEPTH..=1.0).contains(&multiplier), "{}: {} at {} s", name, multiplier, step as f64 / 100.0);
+            }
+        }
+    }
+
+    #[test]
+    fn quiet_segments_dont_pulse() {
+        for (name, effect) in fixtures() {
+            let quiet = effect.analysis.segments.iter().filter(|segment| segment.loudness_max as f64 <= MIN_LOUDNESS);
+
+            for segment in quiet {
+                let middle = (segment.time_interval.start + segment.time_interval.duration / 2.0) as f64;
+                assert_eq!(effect.multiplier_at(middle), 1.0, "{}: pulsing at {} s", name, middle);
+            }
+        }
+    }
+
+    ///
+    /// Records the audio analysis of a track from the Spotify API as a fixture, trimmed to its first `RECORDED_SECONDS`
+    #[test]
+    #[ignore = "needs RSPOTIFY_CLIENT_ID, RSPOTIFY_CLIENT_SECRET and RECORD_TRACK_ID, run with `cargo test record_audio_analysis -- --ignored`"]
+    fn record_audio_analysis() {
+        let spotify = ClientCredsSpotify::new(Credentials::from_env().expect("RSPOTIFY_CLIENT_ID and RSPOTIFY_CLIENT_SECRET must be set"));
+        spotify.request_token().unwrap();
+
+        let track_id = env::var("RECORD_TRACK_ID").expect("RECORD_TRACK_ID must be set");
+        let mut analysis = spotify.track_analysis(TrackId::from_id(track_id.as_str()).unwrap()).unwrap();
+
+        for intervals in [&mut analysis.bars, &mut analysis.beats, &mut analysis.tatums] {
+            intervals.retain(|interval| interval.start < RECORDED_SECONDS);
+        }
+        analysis.sections.retain(|section| section.time_interval.start < RECORDED_SECONDS);
+        analysis.segments.retain(|segment| segment.time_interval.start < RECORDED_SECONDS);
+
+        let path = fixtures_dir().join(format!("audio_analysis_{}.json", track_id));
+        fs::write(&path, serde_json::to_string_pretty(&analysis).unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,62 @@
+use crate::settings::StripLayout;
+use super::base::overlay::{LayerSource, Rgba};
+
+/// Palette of the cover laid out along a 1-D target, see `StripLayout`
+///
+/// `colors` - the palette, most dominant color first
+/// `layout` - how the colors are laid out
+/// `speed` - speed of the chase in LEDs per second
+pub struct PaletteStripLayer {
+    colors: Vec<[f64; 3]>,
+    layout: StripLayout,
+    speed: f64,
+}
+
+impl PaletteStripLayer {
+    pub fn new(colors: &[[u8; 3]], layout: StripLayout, speed: f64) -> Self {
+        let colors = match colors.is_empty() {
+            true => vec![[0.0, 0.0, 0.0]],
+            false => colors.iter().map(|color| color.map(|value| value as f64 / 255.0)).collect(),
+        };
+
+        Self { colors, layout, speed }
+    }
+
+    ///
+    /// Color at `position` along the palette, 0.0 being the first color and 1.0 the last,
+    /// or the first again if `cyclic`
+    fn color_at(&self, position: f64, cyclic: bool) -> [f64; 3] {
+        let segments = match cyclic {
+            true => self.colors.len(),
+            false => self.colors.len() - 1,
+        };
+
+        if segments == 0 {
+            return self.colors[0];
+        }
+
+        let position = position.clamp(0.0, 1.0) * segments as f64;
+        let i = (position.floor() as usize).min(segments - 1);
+        let (from, to) = (self.colors[i], self.colors[(i + 1) % self.colors.len()]);
+        let factor = position - i as f64;
+
+        [0, 1, 2].map(|channel| from[channel] + (to[channel] - from[channel]) * factor)
+    }
+}
+
+impl LayerSource for PaletteStripLayer {
+    fn render(&self, time: f64, num_pixels: usize) -> Vec<Rgba> {
+        let length = num_pixels.max(1) as f64;
+
+        (0..num_pixels).map(|i| {
+            let [r, g, b] = match self.layout {
+                StripLayout::Solid => self.colors[0],
+                StripLayout::Gradient => self.color_at(i as f64 / (length - 1.0).max(1.0), false),
+                StripLayout::Blocks => self.colors[i * self.colors.len() / num_pixels],
+                StripLayout::Chase => self.color_at(((i as f64 + time * self.speed) / length).rem_euclid(1.0), true),
+            };
+
+            [r, g, b, 1.0]
+        }).collect()
+    }
+}
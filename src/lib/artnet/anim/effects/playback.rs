@@ -1,23 +1,26 @@
-use rspotify::model::AudioFeatures;
+use std::sync::Arc;
+use std::time::Instant;
+use rspotify::model::{AudioAnalysis, AudioFeatures};
 
-use super::{base::effect::{EffectBuilder, RenderedEffect}, waveforms::{waveform::WaveformParameters, waveform_impl::{SinEffect, TruncSinEffect}}};
+use super::{base::effect::{EffectBuilder, StreamedEffect}, beat_sync::{BeatSyncEffect, ScheduledFeaturesEffect}, waveforms::{waveform::WaveformParameters, waveform_impl::{SinEffect, TruncSinEffect}}};
 
 
 /// Playback effects built from effects in base module.
 /// 
-/// Effects here use EffectBuilder to create dynamic effects.
+/// Effects here use EffectBuilder to create dynamic effects,
+/// streamed so they can be played for the whole track without precomputing frames.
 /// These should not contain any math functions.
 pub struct PlaybackEffects;
 
 impl PlaybackEffects {
-    pub fn play() -> RenderedEffect {
+    pub fn play() -> StreamedEffect {
         let mut builder = EffectBuilder::new();
         builder.add_brightness_effect(TruncSinEffect, WaveformParameters { amplitude: 0.5, period: 2.0, v_offset: 0.5, h_offset: 0.0, exponent: 1.0 }, 0.5);
 
-        builder.build()
+        builder.build_streamed()
     }
 
-    pub fn pause() -> RenderedEffect {
+    pub fn pause() -> StreamedEffect {
         let mut builder = EffectBuilder::new();
 
         // breathing effect
@@ -34,10 +37,10 @@ impl PlaybackEffects {
             1.0
         );
 
-        builder.build()
+        builder.build_streamed()
     }
 
-    pub fn play_features(features: AudioFeatures) -> RenderedEffect {
+    pub fn play_features(features: &AudioFeatures) -> StreamedEffect {
         // period is doubled since the sin wave crest needs to correspond to each beat
         let period: f64 = (1.0 / (features.tempo / (60.0 * 2.0))) as f64;
         let exponent: f64 = (features.energy * 10.0).round() as f64;
@@ -49,6 +52,46 @@ impl PlaybackEffects {
             1.0
         );
 
-        builder.build()
+        builder.build_streamed()
     }
-}
\ No newline at end of file
+
+    ///
+    /// Slow, shallow breathing over each bar (assumed to be 4 beats), for calm sections without an audio analysis
+    pub fn play_features_calm(features: &AudioFeatures) -> StreamedEffect {
+        let period: f64 = (1.0 / (features.tempo / (60.0 * 2.0))) as f64 * 4.0;
+
+        let mut builder = EffectBuilder::new();
+        builder.add_brightness_effect(
+            SinEffect,
+            WaveformParameters { amplitude: 0.15, period, v_offset: 0.85, h_offset: 0.0, exponent: 1.0 },
+            1.0
+        );
+
+        builder.build_streamed()
+    }
+
+    ///
+    /// Tempo based pulses for tracks without an audio analysis, calmer in the estimated intro and outro,
+    /// see `ScheduledFeaturesEffect`
+    ///
+    /// `position_ms` - position in the track right now, see `PlaybackState::position_ms`
+    /// `duration_ms` - duration of the track, if known
+    pub fn play_features_scheduled(features: &AudioFeatures, position_ms: u64, duration_ms: Option<u64>) -> ScheduledFeaturesEffect {
+        ScheduledFeaturesEffect::new(
+            Self::play_features_calm(features),
+            Self::play_features(features),
+            position_ms as f64 / 1000.0,
+            duration_ms.map(|duration| duration as f64 / 1000.0),
+        )
+    }
+
+    ///
+    /// Pulses on the beats of the track's audio analysis, see `BeatSyncEffect`
+    ///
+    /// `position_ms` - position in the track right now, see `PlaybackState::position_ms`
+    pub fn play_analysis(features: Option<&AudioFeatures>, analysis: Arc<AudioAnalysis>, position_ms: u64) -> BeatSyncEffect {
+        let exponent: f64 = features.map_or(1.0, |features| (features.energy * 10.0).round() as f64);
+
+        BeatSyncEffect::new(analysis, position_ms as f64 / 1000.0, Instant::now(), exponent)
+    }
+}
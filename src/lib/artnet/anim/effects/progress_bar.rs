@@ -0,0 +1,65 @@
+use std::ops::Range;
+use crate::lib::models::playback_state::PlaybackState;
+use super::base::overlay::{LayerSource, Rgba};
+
+// brightness of the remaining part of the bar, relative to the elapsed part
+static REMAINING_BRIGHTNESS: f64 = 0.15;
+
+/// Overlay showing the elapsed / total progress of the track as a bar across the bottom row(s) of a 2-D target
+///
+/// The progress is interpolated from the playback state on every frame, so the bar moves smoothly between polls.
+///
+/// `playback` - the playback state the progress is interpolated from
+/// `size` - width and height of the target
+/// `rows` - rows covered by the bar, from top to bottom
+/// `color` - color of the elapsed part of the bar
+pub struct ProgressBarLayer {
+    playback: PlaybackState,
+    size: (usize, usize),
+    rows: Range<usize>,
+    color: [f64; 3],
+}
+
+impl ProgressBarLayer {
+    ///
+    /// `row` - offset of the bottom of the bar from the bottom row of the target
+    /// `thickness` - number of rows covered by the bar
+    pub fn new(playback: PlaybackState, size: (usize, usize), row: usize, thickness: usize, color: [u8; 3]) -> Self {
+        let bottom = size.1.saturating_sub(row);
+        let rows = bottom.saturating_sub(thickness)..bottom;
+
+        Self {
+            playback,
+            size,
+            rows,
+            color: color.map(|value| value as f64 / 255.0),
+        }
+    }
+}
+
+impl LayerSource for ProgressBarLayer {
+    fn render(&self, _time: f64, num_pixels: usize) -> Vec<Rgba> {
+        let mut pixels: Vec<Rgba> = vec![[0.0, 0.0, 0.0, 0.0]; num_pixels];
+        let (width, height) = self.size;
+
+        let progress = match self.playback.progress_fraction() {
+            Some(progress) if num_pixels == width * height => progress,
+            // nothing is playing, or the frame doesn't cover the target
+            _ => return pixels,
+        };
+
+        // the partially elapsed pixel is blended, so the bar grows smoothly
+        let elapsed = progress * width as f64;
+
+        for y in self.rows.clone() {
+            for x in 0..width {
+                let fill = (elapsed - x as f64).clamp(0.0, 1.0);
+                let brightness = REMAINING_BRIGHTNESS + (1.0 - REMAINING_BRIGHTNESS) * fill;
+
+                pixels[y * width + x] = [self.color[0] * brightness, self.color[1] * brightness, self.color[2] * brightness, 1.0];
+            }
+        }
+
+        pixels
+    }
+}
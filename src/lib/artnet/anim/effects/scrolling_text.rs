@@ -0,0 +1,110 @@
+use super::base::font::{BitmapFont, TextBitmap, GLYPH_HEIGHT};
+use super::base::overlay::{LayerSource, Rgba};
+use crate::lib::models::frame::VALUES_PER_PIXEL;
+
+/// Overlay scrolling a line of text from right to left across a 2-D target, a number of times
+///
+/// Once done scrolling, the layer is transparent, so the animation settles to what's below it.
+///
+/// `text` - the rendered text
+/// `size` - width and height of the target
+/// `top` - row of the top of the text, which is centered vertically
+/// `color` - color of the text
+/// `speed` - scroll speed in pixels per second
+/// `repeat` - number of times the text scrolls across the target
+pub struct ScrollingTextLayer {
+    text: TextBitmap,
+    size: (usize, usize),
+    top: usize,
+    color: [f64; 3],
+    speed: f64,
+    repeat: u32,
+}
+
+impl ScrollingTextLayer {
+    ///
+    /// Renders `text` in the color with the most contrast against the part of `image` it scrolls over
+    ///
+    /// `image` - the image below the text, in row-major RGB
+    /// `palette` - colors to choose from besides black and white, e.g. the palette of the image
+    pub fn new(text: &str, image: &[u8], size: (usize, usize), palette: &[[u8; 3]], speed: f64, repeat: u32) -> Self {
+        let top = size.1.saturating_sub(GLYPH_HEIGHT) / 2;
+        let background = average_color(image, size.0 * top, size.0 * (top + GLYPH_HEIGHT));
+
+        let color = palette.iter()
+            .chain([[255, 255, 255], [0, 0, 0]].iter())
+            .max_by(|a, b| contrast_ratio(**a, background).total_cmp(&contrast_ratio(**b, background)))
+            .copied()
+            .unwrap();
+
+        Self {
+            text: BitmapFont::render(text),
+            size,
+            top,
+            color: color.map(|value| value as f64 / 255.0),
+            speed,
+            repeat,
+        }
+    }
+
+    /// Time in seconds until the text is done scrolling
+    pub fn duration(&self) -> f64 {
+        self.repeat as f64 * (self.size.0 + self.text.width) as f64 / self.speed
+    }
+}
+
+impl LayerSource for ScrollingTextLayer {
+    fn render(&self, time: f64, num_pixels: usize) -> Vec<Rgba> {
+        let mut pixels: Vec<Rgba> = vec![[0.0, 0.0, 0.0, 0.0]; num_pixels];
+        let (width, height) = self.size;
+
+        if time >= self.duration() || num_pixels != width * height {
+            return pixels;
+        }
+
+        // the text starts just past the right edge, and each pass ends once it's past the left edge
+        let distance = (width + self.text.width) as f64;
+        let left = width as f64 - (time * self.speed) % distance;
+
+        for y in 0..self.text.height.min(height - self.top) {
+            for x in 0..width {
+                let text_x = (x as f64 - left).floor();
+
+                if text_x >= 0.0 && self.text.is_lit(text_x as usize, y) {
+                    pixels[(self.top + y) * width + x] = [self.color[0], self.color[1], self.color[2], 1.0];
+                }
+            }
+        }
+
+        pixels
+    }
+}
+
+/// Average color of the pixels `start` (inclusive) to `end` (exclusive) of the image
+fn average_color(image: &[u8], start: usize, end: usize) -> [u8; 3] {
+    let pixels: Vec<&[u8]> = image.chunks_exact(VALUES_PER_PIXEL).skip(start).take(end.saturating_sub(start)).collect();
+
+    if pixels.is_empty() {
+        return [0, 0, 0];
+    }
+
+    [0, 1, 2].map(|channel| (pixels.iter().map(|pixel| pixel[channel] as usize).sum::<usize>() / pixels.len()) as u8)
+}
+
+/// WCAG contrast ratio between two colors, 1.0 (none) - 21.0 (black on white)
+fn contrast_ratio(a: [u8; 3], b: [u8; 3]) -> f64 {
+    let (a, b) = (relative_luminance(a), relative_luminance(b));
+    (a.max(b) + 0.05) / (a.min(b) + 0.05)
+}
+
+fn relative_luminance(color: [u8; 3]) -> f64 {
+    let [r, g, b] = color.map(|value| {
+        let value = value as f64 / 255.0;
+        match value <= 0.03928 {
+            true => value / 12.92,
+            false => ((value + 0.055) / 1.055).powf(2.4),
+        }
+    });
+
+    0.2126 * r + 0.7152 * g + 0.0722 * b
+}
@@ -0,0 +1,151 @@
+use crate::lib::models::frame::{AnimationFrame, VALUES_PER_PIXEL};
+
+// block size of the pixelate transition at its midpoint, as a fraction of the larger side of the target
+static PIXELATE_MAX_BLOCK_FACTOR: f64 = 0.25;
+
+/// Per-pixel transitions for 2-D matrices.
+///
+/// Unlike `TransitionEffects`, these need the width and height of the target,
+/// as which pixel shows which animation depends on its position.
+///
+/// All transitions take the outgoing frame `from`, the incoming frame `to`,
+/// both in row-major RGB, and `progress` (0.0 - 1.0) of the way through the transition.
+pub struct SpatialTransitionEffects;
+
+impl SpatialTransitionEffects {
+    ///
+    /// Reveals `to` from left to right
+    pub fn wipe_horizontal(from: &[u8], to: &[u8], width: usize, height: usize, progress: f64) -> AnimationFrame {
+        let edge = progress.clamp(0.0, 1.0) * width as f64;
+
+        compose(width, height, |x, y| {
+            blend(pixel(from, width, x, y), pixel(to, width, x, y), edge - x as f64)
+        })
+    }
+
+    ///
+    /// Reveals `to` from top to bottom
+    pub fn wipe_vertical(from: &[u8], to: &[u8], width: usize, height: usize, progress: f64) -> AnimationFrame {
+        let edge = progress.clamp(0.0, 1.0) * height as f64;
+
+        compose(width, height, |x, y| {
+            blend(pixel(from, width, x, y), pixel(to, width, x, y), edge - y as f64)
+        })
+    }
+
+    ///
+    /// Slides `to` in from the right, pushing `from` out to the left
+    pub fn slide(from: &[u8], to: &[u8], width: usize, height: usize, progress: f64) -> AnimationFrame {
+        let offset = (progress.clamp(0.0, 1.0) * width as f64).round() as usize;
+
+        compose(width, height, |x, y| {
+            match x + offset < width {
+                true => pixel(from, width, x + offset, y).to_vec(),
+                false => pixel(to, width, x + offset - width, y).to_vec(),
+            }
+        })
+    }
+
+    ///
+    /// Reveals `to` in a circle growing from the center
+    pub fn radial(from: &[u8], to: &[u8], width: usize, height: usize, progress: f64) -> AnimationFrame {
+        let center = ((width as f64 - 1.0) / 2.0, (height as f64 - 1.0) / 2.0);
+        // one more than the distance to the corners, so the corners are fully revealed at the end
+        let radius = progress.clamp(0.0, 1.0) * (center.0.hypot(center.1) + 1.0);
+
+        compose(width, height, |x, y| {
+            let distance = (x as f64 - center.0).hypot(y as f64 - center.1);
+            blend(pixel(from, width, x, y), pixel(to, width, x, y), radius - distance)
+        })
+    }
+
+    ///
+    /// Switches each pixel from `from` to `to` in random order
+    pub fn dissolve(from: &[u8], to: &[u8], width: usize, height: usize, progress: f64) -> AnimationFrame {
+        compose(width, height, |x, y| {
+            match pixel_threshold(y * width + x) < progress {
+                true => pixel(to, width, x, y).to_vec(),
+                false => pixel(from, width, x, y).to_vec(),
+            }
+        })
+    }
+
+    ///
+    /// Pixelates `from` into ever larger blocks during the first half of the transition,
+    /// then sharpens `to` out of the same blocks during the second half
+    pub fn pixelate(from: &[u8], to: &[u8], width: usize, height: usize, progress: f64) -> AnimationFrame {
+        let progress = progress.clamp(0.0, 1.0);
+        let max_block = (width.max(height) as f64 * PIXELATE_MAX_BLOCK_FACTOR).max(1.0);
+
+        let (image, coarseness) = match progress < 0.5 {
+            true => (from, progress * 2.0),
+            false => (to, (1.0 - progress) * 2.0),
+        };
+        let block = (1.0 + (max_block - 1.0) * coarseness).round() as usize;
+
+        if block <= 1 {
+            return AnimationFrame { data: image.to_vec() };
+        }
+
+        compose(width, height, |x, y| {
+            block_average(image, width, height, x / block * block, y / block * block, block)
+        })
+    }
+}
+
+/// Builds a frame by computing each pixel from its coordinates
+fn compose(width: usize, height: usize, pixel_at: impl Fn(usize, usize) -> Vec<u8>) -> AnimationFrame {
+    let mut data = Vec::with_capacity(width * height * VALUES_PER_PIXEL);
+
+    for y in 0..height {
+        for x in 0..width {
+            data.extend(pixel_at(x, y));
+        }
+    }
+
+    AnimationFrame { data }
+}
+
+fn pixel(image: &[u8], width: usize, x: usize, y: usize) -> &[u8] {
+    let start = (y * width + x) * VALUES_PER_PIXEL;
+    &image[start..start + VALUES_PER_PIXEL]
+}
+
+/// Blends two pixels, `factor` is clamped to 0.0 (all `from`) - 1.0 (all `to`) for a one pixel wide soft edge
+fn blend(from: &[u8], to: &[u8], factor: f64) -> Vec<u8> {
+    let factor = factor.clamp(0.0, 1.0);
+
+    from.iter().zip(to.iter())
+        .map(|(from, to)| (*from as f64 * (1.0 - factor) + *to as f64 * factor).round() as u8)
+        .collect()
+}
+
+/// Average color of the `block` x `block` pixels starting at (`x`, `y`), cut off at the edges of the image
+fn block_average(image: &[u8], width: usize, height: usize, x: usize, y: usize, block: usize) -> Vec<u8> {
+    let mut sum = [0usize; VALUES_PER_PIXEL];
+    let mut count = 0;
+
+    for block_y in y..(y + block).min(height) {
+        for block_x in x..(x + block).min(width) {
+            for (total, value) in sum.iter_mut().zip(pixel(image, width, block_x, block_y)) {
+                *total += *value as usize;
+            }
+            count += 1;
+        }
+    }
+
+    sum.iter().map(|total| (total / count.max(1)) as u8).collect()
+}
+
+/// Pseudo-random but fixed threshold (0.0 - 1.0) at which a pixel switches during a dissolve
+fn pixel_threshold(index: usize) -> f64 {
+    // integer hash (from MurmurHash3's finalizer), so neighbouring pixels get unrelated thresholds
+    let mut hash = (index as u32).wrapping_add(0x9e37_79b9);
+    hash ^= hash >> 16;
+    hash = hash.wrapping_mul(0x85eb_ca6b);
+    hash ^= hash >> 13;
+    hash = hash.wrapping_mul(0xc2b2_ae35);
+    hash ^= hash >> 16;
+
+    hash as f64 / u32::MAX as f64
+}
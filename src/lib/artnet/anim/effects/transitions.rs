@@ -1,4 +1,5 @@
-use super::{base::effect::{EffectBuilder, RenderedEffect}, waveforms::{waveform::WaveformParameters, waveform_impl::SawtoothEffect}};
+use crate::lib::models::frame::AnimationFrame;
+use super::{base::effect::{apply_multiplier, EffectBuilder, RenderedEffect}, waveforms::{waveform::WaveformParameters, waveform_impl::SawtoothEffect}};
 
 pub struct TransitionEffects;
 
@@ -26,4 +27,28 @@ impl TransitionEffects {
 
         builder.build()
     }
-}
\ No newline at end of file
+
+    ///
+    /// Blends `from` into `to`, `progress` (0.0 - 1.0) of the way through the transition
+    pub fn crossfade(from: &[u8], to: &[u8], progress: f64) -> AnimationFrame {
+        let progress = progress.clamp(0.0, 1.0);
+
+        AnimationFrame {
+            data: from.iter().zip(to.iter())
+                .map(|(from, to)| (*from as f64 * (1.0 - progress) + *to as f64 * progress).round() as u8)
+                .collect()
+        }
+    }
+
+    ///
+    /// Fades `from` out to black during the first half of the transition,
+    /// then fades `to` in from black during the second half
+    pub fn fade_through_black(from: &[u8], to: &[u8], progress: f64) -> AnimationFrame {
+        let progress = progress.clamp(0.0, 1.0);
+
+        match progress < 0.5 {
+            true => apply_multiplier(from, 1.0 - progress * 2.0),
+            false => apply_multiplier(to, progress * 2.0 - 1.0),
+        }
+    }
+}
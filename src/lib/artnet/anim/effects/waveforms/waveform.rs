@@ -1,6 +1,6 @@
 use crate::settings::SETTINGS;
 
-pub trait WaveformEffect {
+pub trait WaveformEffect: Send + Sync {
     fn render(&self, slice_factor: f64, waveform_params: WaveformParameters) -> Vec<f64> {
         let num_factors = (((f64::from(SETTINGS.read().unwrap().animation.target_fps) * waveform_params.period).round())) as u16;
 
@@ -1,19 +1,32 @@
-use crate::lib::artnet::anim::effects::base::effect::RenderedEffect;
+use crate::lib::artnet::anim::effects::base::font::GLYPH_HEIGHT;
+use crate::lib::artnet::anim::effects::base::overlay::{BlendMode, LayerSource};
+use crate::lib::artnet::anim::effects::palette_strip::PaletteStripLayer;
+use crate::lib::artnet::anim::effects::progress_bar::ProgressBarLayer;
+use crate::lib::artnet::anim::effects::scrolling_text::ScrollingTextLayer;
 use crate::lib::artnet::anim::effects::playback::PlaybackEffects;
-use crate::lib::controllers::artnet::ArtNetController;
-use crate::lib::models::animation::Animation;
+use crate::lib::controllers::artnet::{ArtNetController, StoppedAnimations};
+use crate::lib::models::frame_source::TargetAnimation;
 use crate::lib::models::playback_state::PlaybackState;
-use crate::utils::image::get_image_pixels;
+use crate::lib::models::streamed_animation::StreamedAnimation;
+use crate::lib::models::transition::Transition;
+use crate::utils::image::{get_image_fitted, get_image_pixels, get_image_raw, get_perimeter_pixels, get_region_pixels};
 use std::sync::atomic::Ordering;
+use std::collections::HashMap;
 use std::sync::mpsc::{self, Receiver};
 use std::sync::{Arc, Mutex};
 use std::thread;
 use std::thread::JoinHandle;
-use color_thief::{ColorFormat, get_palette};
-use log::{info, trace};
+use image::DynamicImage;
+use log::{trace, warn};
 use rocket::http::hyper::body::HttpBody;
-use crate::settings::{SETTINGS, Target};
+use serde_json::{json, Value};
+use crate::settings::{FitMode, ProgressBarColor, RestoreMode, SETTINGS, Target, TransitionKind, DEFAULT_CHASE_SPEED, DEFAULT_PALETTE_SIZE, DEFAULT_TRACK_TEXT_SPEED, DEFAULT_TRANSITION_DURATION};
 use crate::utils::network::resolve_ip;
+use crate::utils::palette::get_cover_palette;
+use crate::utils::wled::{get_state, set_state};
+
+// size the cover is rendered to for its palette, independent of the targets
+static COVER_SIZE: u32 = 32;
 
 /////////////////////////////////////////
 /// Public Structs/Enums
@@ -25,7 +38,7 @@ pub struct AnimationControllerConfig {
 
 #[derive(Clone)]
 pub enum AnimationControllerMessage {
-    Animate(PlaybackState),      // start playing animation
+    Animate(Box<PlaybackState>), // start playing animation
     Stop,       // stop animation
     Timeout,    // timeout animation
     Terminate,  // terminate the message loop
@@ -36,10 +49,12 @@ pub enum AnimationControllerMessage {
 ///
 /// `artnet_controller` - the controller for the target ArtNet device
 /// `active_animation` - thread of the currently playing animation
+/// `snapshots` - WLED state of each target (by host) from before the animation took over
 ///
 pub struct AnimationController {
     artnet_controller: Arc<ArtNetController>,
     rx_app: Arc<Mutex<Receiver<AnimationControllerMessage>>>,
+    snapshots: Arc<Mutex<HashMap<String, Value>>>,
 }
 
 impl AnimationController {
@@ -48,13 +63,15 @@ impl AnimationController {
 
         Self {
             artnet_controller: Arc::new(artnet_controller),
-            rx_app: Arc::new(Mutex::new(rx_app))
+            rx_app: Arc::new(Mutex::new(rx_app)),
+            snapshots: Arc::new(Mutex::new(HashMap::new())),
         }
     }
 
     pub fn start(&self) {
         let local_artnet_controller = self.artnet_controller.clone();
         let local_receiver = self.rx_app.clone();
+        let local_snapshots = self.snapshots.clone();
 
         thread::spawn(move || {
             let mut current_playing: PlaybackState = PlaybackState::none();
@@ -64,21 +81,23 @@ impl AnimationController {
             loop {
                 match receiver_guard.recv() {
                     Ok(AnimationControllerMessage::Animate(playback)) => {
-                        AnimationController::play_from_playback(local_artnet_controller.as_ref(), playback.clone());
-                        current_playing = playback;
+                        let track_changed = playback.track_id.is_some() && playback.track_id != current_playing.track_id;
+                        AnimationController::play_from_playback(local_artnet_controller.as_ref(), &local_snapshots, *playback.clone(), track_changed);
+                        current_playing = *playback;
                     },
                     // for handling messages when loop is not running
                     Ok(AnimationControllerMessage::Stop) => {
-                        local_artnet_controller.stop_animation();
+                        AnimationController::stop_and_restore(local_artnet_controller.as_ref(), &local_snapshots);
                     },
                     // timeout signal received
                     Ok(AnimationControllerMessage::Timeout) => {
                         if PlaybackState::eq(&current_playing, &PlaybackState::none()) {
-                            local_artnet_controller.stop_animation();
+                            AnimationController::stop_and_restore(local_artnet_controller.as_ref(), &local_snapshots);
                         }
                     },
                     // terminate the entire controller
                     Ok(AnimationControllerMessage::Terminate) => {
+                        AnimationController::stop_and_restore(local_artnet_controller.as_ref(), &local_snapshots);
                         break;
                     },
                     Err(mpsc::RecvError) => {
@@ -92,7 +111,7 @@ impl AnimationController {
     /// Plays the given animation to the target device.
     ///
     /// If an animation is already playing, it set the stop flag, wait for it to complete,
-    /// then starts the new animation.
+    /// then starts the new animation, transitioning from the previous one.
     ///
     /// `animation` - the animation to be played
     ///
@@ -100,39 +119,62 @@ impl AnimationController {
     ///     A Result indicating the success of the operation
     ///
     /// Plays animation according to the given PlaybackState
-    fn play_from_playback(artnet_controller: &ArtNetController, playback: PlaybackState) {
+    ///
+    /// `track_changed` - whether a new track started playing, rather than e.g. the same one being paused
+    fn play_from_playback(artnet_controller: &ArtNetController, snapshots: &Mutex<HashMap<String, Value>>, playback: PlaybackState, track_changed: bool) {
+        let local_playback = playback.clone();
+        let position_ms = playback.position_ms().unwrap_or(0);
+
         let image_thread = thread::spawn(move || {
-            let image = get_image_pixels(playback.cover_url, &32, &32).unwrap();
-            image
+            playback.cover_url.and_then(|url| match get_image_raw(&url) {
+                Ok(image) => Some(image),
+                Err(e) => {
+                    warn!("Unable to load cover {}, using a blank one: {}", url, e);
+                    None
+                },
+            })
         });
 
+        // the style changes at the sections of the audio analysis if available, or at the estimated intro and outro otherwise
         let effect_thread = thread::spawn(move || {
-            let effect: RenderedEffect = match (playback.is_playing, playback.features) {
-                (true, Some(features)) => {
-                    PlaybackEffects::play_features(features)
+            let effect: Arc<dyn LayerSource> = match (playback.is_playing, playback.features, playback.analysis) {
+                (true, features, Some(analysis)) => {
+                    Arc::new(PlaybackEffects::play_analysis(features.as_ref(), analysis, position_ms))
                 },
-                (true, None) => {
-                    PlaybackEffects::play()
+                (true, Some(features), None) => {
+                    Arc::new(PlaybackEffects::play_features_scheduled(&features, position_ms, playback.duration_ms))
                 },
-                (false, _) => {
-                    PlaybackEffects::pause()
+                (true, None, None) => {
+                    Arc::new(PlaybackEffects::play())
+                },
+                (false, _, _) => {
+                    Arc::new(PlaybackEffects::pause())
                 }
             };
             effect
         });
 
-        let animation_thread: JoinHandle<Vec<Animation>> = thread::spawn(move || {
-            let devices = SETTINGS.read().unwrap().targets.to_vec();
+        let animation_thread: JoinHandle<Vec<TargetAnimation>> = thread::spawn(move || {
+            let (devices, canvas_settings) = {
+                let settings = SETTINGS.read().unwrap();
+                (settings.targets.to_vec(), settings.canvas.clone())
+            };
             let image = image_thread.join().unwrap();
+            let cover = get_image_pixels(image.as_ref(), &COVER_SIZE, &COVER_SIZE, &FitMode::Stretch, &[0, 0, 0]);
+            // rendered once, so the targets on it show seamless parts of the same image
+            let canvas = canvas_settings.map(|canvas| {
+                get_image_fitted(image.as_ref(), &(canvas.size.0 as u32), &(canvas.size.1 as u32), &canvas.fit, &canvas.background.unwrap_or([0, 0, 0]))
+            });
             let effect = effect_thread.join().unwrap();
 
             devices.iter().map(|device| {
-                AnimationController::get_animation_for_device(device, &image, &effect)
-            }).collect::<Vec<Animation>>()
+                let pixels = AnimationController::get_target_pixels(device, image.as_ref(), canvas.as_ref());
+                AnimationController::get_animation_for_device(device, pixels, &cover, &effect, &local_playback, track_changed)
+            }).collect::<Vec<TargetAnimation>>()
         });
 
         // if some animation is already playing, stop it
-        if artnet_controller.any_playing() {
+        let previous = if artnet_controller.any_playing() {
             // don't stop animation until next one is rendered
             while !animation_thread.is_finished() {}
             trace!("Next animation rendered, stopping previous animation");
@@ -141,10 +183,21 @@ impl AnimationController {
             trace!("Set stop flag; waiting for previous animation to stop");
 
             // wait for all previous animation to stop before sending new one
-            while artnet_controller.any_playing() {}
-        }
+            artnet_controller.wait_stopped()
+        } else {
+            // nothing is playing, so the targets are about to be taken over
+            // (drop the animations left over from before the targets were restored)
+            artnet_controller.take_stopped();
+            AnimationController::snapshot_targets(snapshots);
+            None
+        };
+
+        let animations = animation_thread.join().unwrap();
 
-        artnet_controller.send_animations(animation_thread.join().unwrap());
+        artnet_controller.send_animations(match previous {
+            Some(previous) => AnimationController::add_transitions(previous, animations),
+            None => animations,
+        });
         trace!("New animation sent");
     }
 
@@ -153,30 +206,202 @@ impl AnimationController {
     }
 
     ///
-    /// Renders an animation for the given device, image, and effect.
-    fn get_animation_for_device(device: &Target, image: &Vec<u8>, effect: &RenderedEffect) -> Animation {
-        match device.size {
-            // 1-dimensional effect
-            // NOTE: currently this only supports DMX mode `Single RGB`, not `Multi RGB` (one color for the entire target)
+    /// Wraps each animation in a transition from the animation previously playing on the same target,
+    /// according to the `[animation]` transition settings.
+    fn add_transitions(previous: StoppedAnimations, animations: Vec<TargetAnimation>) -> Vec<TargetAnimation> {
+        let (kind, duration) = {
+            let settings = SETTINGS.read().unwrap();
+            (settings.animation.transition, settings.animation.transition_duration.unwrap_or(DEFAULT_TRANSITION_DURATION))
+        };
+
+        if kind == TransitionKind::Cut || duration <= 0.0 {
+            return animations;
+        }
+
+        let mut previous_animations = previous.animations;
+
+        animations.into_iter().map(|animation| {
+            match previous_animations.iter().position(|previous| previous.device.host == animation.device.host) {
+                Some(i) => TargetAnimation {
+                    source: Box::new(Transition::new(
+                        previous_animations.swap_remove(i).source,
+                        previous.tick,
+                        previous.elapsed,
+                        animation.source,
+                        kind,
+                        duration,
+                        animation.device.size(),
+                    )),
+                    ..animation
+                },
+                None => animation,
+            }
+        }).collect()
+    }
+
+    ///
+    /// Stops the animation and restores the targets, blocking until done.
+    ///
+    /// NOTE: this makes blocking HTTP requests, so it must not be called from an async context (e.g. a route handler)
+    pub fn shutdown(&self) {
+        AnimationController::stop_and_restore(self.artnet_controller.as_ref(), &self.snapshots);
+    }
+
+    /// Stops the animation, waits for it to stop, then restores each target according to its `restore` setting
+    fn stop_and_restore(artnet_controller: &ArtNetController, snapshots: &Mutex<HashMap<String, Value>>) {
+        artnet_controller.stop_animation();
+        artnet_controller.wait_stopped();
+
+        let devices = SETTINGS.read().unwrap().targets.to_vec();
+        let mut snapshots_guard = snapshots.lock().unwrap();
+
+        for device in devices.iter() {
+            // `live: false` makes WLED leave realtime mode immediately, instead of after the realtime timeout
+            let state = match device.restore {
+                RestoreMode::State => match snapshots_guard.remove(&device.host) {
+                    Some(mut snapshot) => {
+                        snapshot["live"] = json!(false);
+                        snapshot
+                    },
+                    None => continue,
+                },
+                RestoreMode::Preset => json!({ "ps": device.restore_preset, "live": false }),
+                RestoreMode::Off => json!({ "on": false, "live": false }),
+                RestoreMode::None => continue,
+            };
+
+            match set_state(&device.host, &state) {
+                Ok(_) => trace!("Restored state of {}", &device.host),
+                Err(e) => warn!("Unable to restore state of {}: {}", &device.host, e),
+            }
+        }
+    }
+
+    /// Saves the WLED state of each target that restores its previous state, unless already saved
+    fn snapshot_targets(snapshots: &Mutex<HashMap<String, Value>>) {
+        let devices = SETTINGS.read().unwrap().targets.to_vec();
+        let mut snapshots_guard = snapshots.lock().unwrap();
+
+        for device in devices.iter().filter(|device| device.restore == RestoreMode::State) {
+            if snapshots_guard.contains_key(&device.host) {
+                continue;
+            }
+
+            match get_state(&device.host) {
+                Ok(state) => {
+                    trace!("Saved state of {}", &device.host);
+                    snapshots_guard.insert(device.host.clone(), state);
+                },
+                Err(e) => warn!("Unable to save state of {}, it will not be restored: {}", &device.host, e),
+            }
+        }
+    }
+
+    ///
+    /// Renders the cover for the given device: its region of the canvas if it is on the canvas,
+    /// otherwise the entire cover according to its fit mode.
+    ///
+    /// Returns:
+    ///     the pixels in row-major RGB, empty for 1-D targets not on the canvas (which show the palette instead)
+    fn get_target_pixels(device: &Target, image: Option<&DynamicImage>, canvas: Option<&DynamicImage>) -> Vec<u8> {
+        let region = device.canvas_region().map(|(x, y, width, height)| (x as u32, y as u32, width as u32, height as u32));
+
+        match (device.size(), region, canvas) {
+            ((length, 0), Some(region), Some(canvas)) => get_perimeter_pixels(canvas, region, length as usize),
+            ((width, height), Some(region), Some(canvas)) => get_region_pixels(canvas, region, &(width as u32), &(height as u32)),
+            ((_, 0), _, _) => Vec::new(),
+            ((width, height), _, _) => {
+                get_image_pixels(image, &(width as u32), &(height as u32), &device.fit, &device.background.unwrap_or([0, 0, 0]))
+            },
+        }
+    }
+
+    ///
+    /// Renders an animation for the given device, image, and effect,
+    /// with the target's overlays (e.g. progress bar) for the given playback on top.
+    ///
+    /// `pixels` - the cover rendered to the target, see `get_target_pixels`
+    /// `cover` - the cover at `COVER_SIZE`, the palette is taken from
+    /// `track_changed` - whether to scroll the artist and title of the track across the target
+    fn get_animation_for_device(device: &Target, pixels: Vec<u8>, cover: &[u8], effect: &Arc<dyn LayerSource>, playback: &PlaybackState, track_changed: bool) -> TargetAnimation {
+        let palette = get_cover_palette(cover, 2);
+
+        let text = match (&playback.artist_name, &playback.track_name) {
+            (Some(artist), Some(track)) => Some(format!("{} – {}", artist, track)),
+            (None, Some(track)) => Some(track.clone()),
+            _ => None,
+        };
+
+        // built before the pixels are moved into the animation, and layered on top of it at the end
+        let track_text = match (device.size(), device.track_text(), text) {
+            ((width, height), Some(track_text), Some(text)) if track_changed && height as usize >= GLYPH_HEIGHT => {
+                Some(ScrollingTextLayer::new(
+                    &text,
+                    &pixels,
+                    (width as usize, height as usize),
+                    &palette,
+                    track_text.speed.unwrap_or(DEFAULT_TRACK_TEXT_SPEED),
+                    track_text.repeat.unwrap_or(1) as u32,
+                ))
+            },
+            _ => None,
+        };
+
+        let mut animation = match device.size() {
+            // part of the canvas, laid out along the edge of its region
+            (_, 0) if !pixels.is_empty() => StreamedAnimation::new(pixels, effect.clone()),
+            // 1-dimensional effect, the palette of the cover laid out along the target
             // TODO: add support for WLED `Effect` ArtNet mode
             (_, 0) => {
-                let palette = get_palette(&image, ColorFormat::Rgb, 1, 2)
-                    .unwrap()  // TODO: add default palette
-                    .into_iter()
-                    .nth(0).unwrap();
-
-                Animation::new(
-                    resolve_ip(device.host.clone().as_str()).unwrap(),  // Clone the host to avoid moving it
-                    vec![palette.r, palette.g, palette.b],        // Clone the image so it can be reused
-                    effect.clone(),       // Clone the effect so it can be reused
+                let strip = device.strip.clone().unwrap_or_default();
+                let palette_size = strip.palette_size.unwrap_or(DEFAULT_PALETTE_SIZE);
+
+                let colors = get_cover_palette(cover, palette_size as usize);
+
+                // DMX targets in WLED's `Single RGB` mode show one color for the entire target,
+                // other layouts and non-DMX protocols address each LED
+                StreamedAnimation::with_base(
+                    PaletteStripLayer::new(&colors, strip.layout, strip.chase_speed.unwrap_or(DEFAULT_CHASE_SPEED)),
+                    device.num_sent_pixels(),
+                    effect.clone(),     // Clone the effect so it can be reused
                 )
             },
             // 2-dimensional effect
-            (_, _) => Animation::new(
-                resolve_ip(device.host.clone().as_str()).unwrap(),  // Clone the host to avoid moving it
-                image.clone(),        // Clone the image so it can be reused
-                effect.clone(),       // Clone the effect so it can be reused
-            ),
+            (_, _) => StreamedAnimation::new(pixels, effect.clone()),        // Clone the effect so it can be reused
+        };
+
+        match (device.size(), device.progress_bar()) {
+            // progress bar, only while a track is loaded
+            ((width, height), Some(progress_bar)) if height > 0 && playback.duration_ms.is_some() => {
+                let color = match progress_bar.color {
+                    ProgressBarColor::Dominant => palette.first(),
+                    ProgressBarColor::Accent => palette.get(1).or(palette.first()),
+                    ProgressBarColor::White => None,
+                }.copied().unwrap_or([255, 255, 255]);
+
+                animation.add_layer(
+                    ProgressBarLayer::new(
+                        playback.clone(),
+                        (width as usize, height as usize),
+                        progress_bar.row.unwrap_or(0) as usize,
+                        progress_bar.thickness.unwrap_or(1) as usize,
+                        color,
+                    ),
+                    BlendMode::AlphaOver,
+                    1.0,
+                );
+            },
+            _ => {},
+        }
+
+        if let Some(track_text) = track_text {
+            animation.add_layer(track_text, BlendMode::AlphaOver, 1.0);
+        }
+
+        TargetAnimation {
+            target: resolve_ip(device.host.clone().as_str()).unwrap(),  // Clone the host to avoid moving it
+            device: device.clone(),
+            source: Box::new(animation),
         }
     }
 }
@@ -87,10 +87,20 @@ impl ApplicationController {
 
     pub fn stop(&self) {
         self.stop_flag.store(true, Ordering::Relaxed);
-        self.animation_controller.stop_animation();
+        // stopped from the animation thread, as restoring the targets makes blocking requests
+        self.anim_msg_tx.send(AnimationControllerMessage::Stop).unwrap();
         self.sp_msg_tx.send(SpotifyControllerMessage::Terminate).unwrap();
     }
 
+    ///
+    /// Stops everything and restores the targets, blocking until done.
+    /// To be called when the application exits.
+    pub fn shutdown(&self) {
+        self.stop_flag.store(true, Ordering::Relaxed);
+        let _ = self.sp_msg_tx.send(SpotifyControllerMessage::Terminate);
+        self.animation_controller.shutdown();
+    }
+
     // ///
     // /// Request access token using callback response
     // /// 
@@ -110,7 +120,7 @@ impl ApplicationController {
                 match local_receiver.lock().unwrap().try_recv() {
                     // new playback state found, play it
                     Ok(new_playback) => {
-                        local_anim_msg_tx.send(AnimationControllerMessage::Animate(new_playback.clone())).unwrap();
+                        local_anim_msg_tx.send(AnimationControllerMessage::Animate(Box::new(new_playback.clone()))).unwrap();
 
                         if PlaybackState::eq(&new_playback, &PlaybackState::none()) {
                             let local_local_sp_msg_tx = local_sp_msg_tx.clone();
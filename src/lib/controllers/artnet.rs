@@ -1,25 +1,60 @@
-use std::net::{UdpSocket, ToSocketAddrs};
+use std::borrow::Cow;
+use std::net::UdpSocket;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
-use std::{cmp, thread};
+use std::thread;
 use std::thread::JoinHandle;
-use artnet_protocol::*;
-use log::trace;
-use crate::lib::models::animation::Animation;
+use log::{info, trace, warn};
 use crate::lib::models::frame::AnimationFrame;
+use crate::lib::models::frame_clock::FrameClock;
+use crate::lib::models::frame_source::TargetAnimation;
+use crate::lib::models::channel_encoder::ChannelEncoder;
+use crate::lib::models::color_correction::ColorCorrection;
+use crate::lib::models::pixel_map::PixelMap;
+use crate::lib::protocols::protocol::{get_protocol, OutputProtocol};
 use crate::settings::SETTINGS;
 
+/// Animations of a stopped animation thread, so the next animation can transition from them
+///
+/// `animations` - the animations that were playing
+/// `tick` - tick the animations were stopped at
+/// `elapsed` - time in seconds the animations were stopped at
+pub struct StoppedAnimations {
+    pub animations: Vec<TargetAnimation>,
+    pub tick: usize,
+    pub elapsed: f64,
+}
+
+/// Conversion of composed (row-major RGB) frames into what a target device expects, applied right before sending
+///
+/// Created for each `send_animations`, so state (e.g. the dithering errors of the color correction)
+/// isn't carried over from the previous animation.
+///
+/// `color_correction` - the color correction of the target device, if calibrated
+/// `pixel_map` - the wiring of the target device, if not row-major
+/// `channel_encoder` - the channel layout of the target device, if not RGB
+/// `send_failed` - whether sending the last frame failed (e.g. host unreachable), so the error is only logged once
+struct TargetOutput {
+    color_correction: Option<ColorCorrection>,
+    pixel_map: Option<PixelMap>,
+    channel_encoder: Option<ChannelEncoder>,
+    send_failed: bool,
+}
+
 /// Controller module for ArtNet devices
 /// 
 /// A UDPSocket will be connected throughout the lifecycle of the controller.
 /// Once destroyed, the UDPSocket connection will be terminated.
 /// 
-/// `target` - address of the target ArtNet device, without port
-/// `dimensions` - height and weight of the target device
+/// Frames are encoded with the output protocol configured for each target (see `protocols` module),
+/// so despite the name, this also drives non-ArtNet targets.
 /// 
+/// `active_animations` - threads of the currently playing animations
+/// `stop_flag` - signals the animation threads to play their out transitions and stop
+/// `socket` - socket shared by all targets
 pub struct ArtNetController {
-    active_animations: Arc<Mutex<Vec<JoinHandle<()>>>>,
+    active_animations: Arc<Mutex<Vec<JoinHandle<StoppedAnimations>>>>,
     stop_flag: Arc<AtomicBool>,
     socket: UdpSocket,
 }
@@ -27,6 +62,8 @@ impl ArtNetController {
     pub fn new() -> Self {
         let stop_flag = Arc::new(AtomicBool::new(false));
         let socket = UdpSocket::bind("0.0.0.0:0").expect("Unable to bind to address!");
+        // ArtNet targets may be broadcast addresses
+        socket.set_broadcast(true).expect("Unable to enable broadcast!");
 
         Self {
             active_animations: Arc::new(Mutex::new(Vec::new())),
@@ -36,55 +73,83 @@ impl ArtNetController {
     }
 
     ///
-    /// Sends animations to the respective device specified in each Animation struct.
+    /// Sends animations to the respective device specified in each TargetAnimation struct.
+    ///
+    /// All animations are driven from a single thread by one `FrameClock`,
+    /// so the targets stay phase-locked to each other.
     ///
     /// WARNING: before using, the caller must ensure with ArtNetController::any_playing() that no animations are currently playing.
     ///
-    pub fn send_animations(&self, animations: Vec<Animation>) {
+    pub fn send_animations(&self, animations: Vec<TargetAnimation>) {
         // reset stop flag for new animation
         self.stop_flag.store(false, Ordering::Release);
 
-        for animation in animations {
-            let local_stop_flag = self.stop_flag.clone();
-            let local_socket = self.socket.try_clone().expect("Unable to clone socket!");
-            let local_target = animation.target.clone();
-
-            let handle = thread::spawn(move || {
-                // for tracking frame sequence
-                // all shards within the same frame will have the same sequence number
-                let mut sequence_counter: u8 = 0;
-
-                // TODO: transitions
-                if !animation.frames_in.is_none() {
-                    for frame in animation.frames_in.clone().unwrap().clone() {
-                        ArtNetController::send_frame(&local_target, &animation.get_frame_pixels(), frame, sequence_counter, &local_socket);
-                    }
+        let local_stop_flag = self.stop_flag.clone();
+        let local_socket = self.socket.try_clone().expect("Unable to clone socket!");
+        let frame_interval = SETTINGS.read().unwrap().animation.frame_interval;
+
+        let handle = thread::spawn(move || {
+            let mut outputs: Vec<(TargetAnimation, Box<dyn OutputProtocol>, TargetOutput)> = animations.into_iter().map(|animation| {
+                let protocol = get_protocol(&animation.target, &animation.device);
+                let output = TargetOutput {
+                    color_correction: ColorCorrection::new(&animation.device),
+                    pixel_map: PixelMap::new(&animation.device),
+                    channel_encoder: ChannelEncoder::new(&animation.device),
+                    send_failed: false,
+                };
+                (animation, protocol, output)
+            }).collect();
+
+            let mut clock = FrameClock::new(Duration::from_secs_f64(frame_interval));
+
+            // in transition and loop, until stopped
+            while !local_stop_flag.load(Ordering::Acquire) {
+                let tick = clock.tick() as usize;
+
+                for (animation, protocol, output) in outputs.iter_mut() {
+                    let frame = animation.source.get_frame(tick, tick as f64 * frame_interval);
+                    ArtNetController::send_frame(protocol.as_mut(), output, &frame, &local_socket);
                 }
+            }
+            trace!("Breaking out of animation loop");
 
-                while !local_stop_flag.load(Ordering::Acquire) {
-                    for frame in animation.frames_loop.clone() {
-                        ArtNetController::send_frame(&local_target, &animation.get_frame_pixels(), frame, sequence_counter, &local_socket);
+            // out transition, until every source has finished its out transition
+            let stop_tick = clock.tick() as usize;
+            let stop_elapsed = stop_tick as f64 * frame_interval;
+            let mut out_tick = 0;
 
-                        // to allow for termination mid-animation
-                        if local_stop_flag.load(Ordering::Acquire) {
-                            trace!("Breaking out of animation loop for target: {}", local_target);
-                            break;
-                        }
+            loop {
+                let mut any_sent = false;
 
-                        sequence_counter = sequence_counter.wrapping_add(1);
+                for (animation, protocol, output) in outputs.iter_mut() {
+                    if let Some(frame) = animation.source.get_frame_out(out_tick, out_tick as f64 * frame_interval) {
+                        ArtNetController::send_frame(protocol.as_mut(), output, &frame, &local_socket);
+                        any_sent = true;
                     }
                 }
 
-                // TODO: transitions
-                if !animation.frames_out.is_none() {
-                    for frame in animation.frames_out.clone().unwrap().clone() {
-                        ArtNetController::send_frame(&local_target, &animation.get_frame_pixels(), frame, sequence_counter, &local_socket);
-                    }
+                if !any_sent {
+                    break;
                 }
-            });
 
-            self.active_animations.lock().unwrap().push(handle);
-        }
+                out_tick = clock.tick() as usize - stop_tick;
+            }
+
+            if clock.missed() > 0 {
+                warn!("Missed {} frame deadline(s) while playing animation, consider lowering target FPS", clock.missed());
+            }
+
+            StoppedAnimations {
+                animations: outputs.into_iter().map(|(animation, _, _)| TargetAnimation {
+                    source: animation.source.settle(stop_elapsed),
+                    ..animation
+                }).collect(),
+                tick: stop_tick,
+                elapsed: stop_elapsed,
+            }
+        });
+
+        self.active_animations.lock().unwrap().push(handle);
     }
 
     pub fn stop_animation(&self) {
@@ -97,67 +162,80 @@ impl ArtNetController {
     }
 
     /// Checks if any animation threads are still active.
-    /// Automatically drops animation threads that have finished from tracker.
     pub fn any_playing(&self) -> bool {
+        self.active_animations.lock().unwrap().iter().any(|handle| !handle.is_finished())
+    }
+
+    /// Waits for all animation threads to finish (e.g. after `stop_animation`), without busy waiting.
+    ///
+    /// Returns:
+    ///     the animations of the last finished thread, if any
+    pub fn wait_stopped(&self) -> Option<StoppedAnimations> {
+        // taken out of the tracker, so it isn't locked while joining
+        let handles: Vec<JoinHandle<StoppedAnimations>> = self.active_animations.lock().unwrap().drain(..).collect();
+
+        handles.into_iter()
+            .filter_map(|handle| handle.join().ok())
+            .last()
+    }
+
+    /// Drops animation threads that have finished from tracker.
+    ///
+    /// Returns:
+    ///     the animations of the last finished thread, if any
+    pub fn take_stopped(&self) -> Option<StoppedAnimations> {
         let mut active_animations_guard = self.active_animations.lock().unwrap();
-        // remove threads that have finished
-        active_animations_guard.retain(|handle| !handle.is_finished());
-        !active_animations_guard.is_empty()
+        let (finished, active) = active_animations_guard.drain(..).partition(|handle| handle.is_finished());
+        *active_animations_guard = active;
+
+        finished.into_iter()
+            .filter_map(|handle: JoinHandle<StoppedAnimations>| handle.join().ok())
+            .last()
     }
 
     /// Sends a single frame (or image) to the target device
     ///
+    /// `protocol` - the output protocol of the target device
+    /// `output` - how frames are converted for the target device
     /// `frame` - the frame to be sent
     ///
     fn send_frame(
-        target: &String,
-        target_size: &u16,
-        frame: AnimationFrame,
-        sequence_counter: u8,
+        protocol: &mut dyn OutputProtocol,
+        output: &mut TargetOutput,
+        frame: &AnimationFrame,
         socket: &UdpSocket,
     ) {
-        let addr = format!("{}:6454", target).to_socket_addrs().unwrap().next().unwrap();
-        let commands = Self::calculate_sharded_commands(target_size, frame, sequence_counter);
-
-        for command_byte in commands {
-            socket.send_to(&command_byte, &addr).unwrap();
-        }
-
-        thread::sleep(Duration::from_secs_f64(SETTINGS.read().unwrap().animation.frame_interval));
-    }
-
-    // NOTE: size is the number of pixels in the target device, dimension-agnostic
-    fn calculate_sharded_commands(size: &u16, frame: AnimationFrame, mut sequence_counter: u8) -> Vec<Vec<u8>> {
-        // or channels per universe
-        static CHANNELS_PER_SHARD: u16 = 510;
-        static CHANNELS_PER_PIXEL: u16 = 3;
-
-        let mut commands: Vec<Vec<u8>> = Vec::new();
-
-        // calculate number of shards needed
-        // total num of channels in frame = (width * height) * (channels per pixel, 3)
-        // we can fit only 170 pixels/510 channels in a single universe, even though the max is 512
-        // num of shards = ceil(total num of channels / 510)
-        let num_shards: u16 = (
-            (((size * CHANNELS_PER_PIXEL) as u32) / CHANNELS_PER_SHARD as u32) + 1
-        ) as u16;
-
-        for u in 0..num_shards {
-            let start: usize = (u * CHANNELS_PER_SHARD) as usize;
-            let end: usize = cmp::min(((u + 1) * CHANNELS_PER_SHARD) - 1, (frame.data.len() - 1) as u16) as usize;
-            let frame_slice = frame.data[start..=end].to_vec();
-            let command: ArtCommand = ArtCommand::Output(Output {
-                data: frame_slice.into(), // The data we're sending to the node
-                sequence: sequence_counter,
-                port_address: PortAddress::try_from(u as u8).unwrap(),
-                ..Output::default()
-            });
-
-            let bytes = command.write_to_buffer().unwrap();
-
-            commands.push(bytes);
+        let frame = match &mut output.color_correction {
+            Some(color_correction) => color_correction.apply(frame),
+            None => Cow::Borrowed(frame),
+        };
+        let frame = match &output.pixel_map {
+            Some(pixel_map) => Cow::Owned(pixel_map.apply(&frame).into_owned()),
+            None => frame,
+        };
+        // after the pixel map, which reorders RGB pixels
+        let frame = match &output.channel_encoder {
+            Some(channel_encoder) => Cow::Owned(channel_encoder.apply(&frame).into_owned()),
+            None => frame,
+        };
+
+        // all targets are driven from the same thread, so a target failing must not stop the others
+        for packet in protocol.encode_frame(&frame) {
+            match socket.send_to(&packet.data, packet.addr) {
+                Ok(_) if output.send_failed => {
+                    info!("Sending frames to {} again", packet.addr);
+                    output.send_failed = false;
+                },
+                Ok(_) => {},
+                Err(e) => {
+                    // the rest of the frame is skipped, the next frame is tried again
+                    if !output.send_failed {
+                        warn!("Unable to send frame to {}, skipping frames until it is reachable: {}", packet.addr, e);
+                        output.send_failed = true;
+                    }
+                    return;
+                },
+            }
         }
-
-        commands
     }
 }
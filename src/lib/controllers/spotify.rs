@@ -3,7 +3,7 @@ use std::sync::Mutex;
 use std::sync::Arc;
 use std::thread::{self};
 use std::time::Duration;
-use log::{debug, info, trace};
+use log::{debug, info, trace, warn};
 use rspotify::model::{AdditionalType, TrackId};
 use rspotify::{AuthCodeSpotify, ClientError, Token};
 use rspotify::clients::{BaseClient, OAuthClient};
@@ -178,19 +178,34 @@ impl SpotifyController {
             }
         };
 
-        // check if state has changed
-        if !PlaybackState::eq(&new_playback, &current_playing) {
-            // if state has changed, get audio features and return `true`
+        // check if state has changed, or the track was seeked so the beats need to be re-synced
+        if !PlaybackState::eq(&new_playback, &current_playing) || new_playback.is_seeked(current_playing) {
+            // if state has changed, get audio features and analysis and return `true`
             let track_id: Option<TrackId> = match new_playback.track_id.as_ref() {
                 Some(id) => Some(TrackId::from_id(id).unwrap()),
                 None => None,
             };
             
             match track_id {
+                // same track (e.g. paused, resumed or seeked), no need to fetch them again
+                Some(_) if new_playback.track_id == current_playing.track_id => {
+                    new_playback.add_features(current_playing.features.clone());
+                    new_playback.add_analysis(current_playing.analysis.clone());
+                },
                 Some(id) => {
-                    new_playback.add_features(
-                        Some(client.track_features(id).unwrap())
-                    );
+                    let features = client.track_features(id.clone()).unwrap();
+
+                    // without the analysis, effects fall back to the tempo from the audio features
+                    let analysis = match client.track_analysis(id) {
+                        Ok(analysis) => Some(Arc::new(analysis)),
+                        Err(e) => {
+                            warn!("Unable to get audio analysis, beats will not be synced: {}", e);
+                            None
+                        },
+                    };
+
+                    new_playback.add_features(Some(features));
+                    new_playback.add_analysis(analysis);
                 },
                 None => {},
             }
@@ -1,22 +1,22 @@
+use std::borrow::Cow;
 use crate::lib::artnet::anim::effects::base::effect::RenderedEffect;
-use super::frame::AnimationFrame;
-
-static VALUES_PER_PIXEL: usize = 3;
+use super::frame::{AnimationFrame, VALUES_PER_PIXEL};
+use super::frame_source::FrameSource;
 
+/// Animation with all of its frames precomputed from a `RenderedEffect`
 #[derive(Clone)]
 pub struct Animation {
     pub frames_loop: Vec<AnimationFrame>,
     // optional in/out transition frames
     pub frames_in: Option<Vec<AnimationFrame>>,
     pub frames_out: Option<Vec<AnimationFrame>>,
-    pub target: String,
     image: Vec<u8>,
 }
 
 impl Animation {
-    pub fn new(target: String, image: Vec<u8>, effect: RenderedEffect) -> Self {
+    pub fn new(image: Vec<u8>, effect: RenderedEffect) -> Self {
         let frames_loop = effect.apply(&image);
-        Self { frames_loop, frames_in: None, frames_out: None, target, image }
+        Self { frames_loop, frames_in: None, frames_out: None, image }
     }
 
     pub fn add_transition_in(&mut self, effect: RenderedEffect) {
@@ -32,4 +32,25 @@ impl Animation {
     pub fn get_frame_pixels(&self) -> u16 {
         (self.frames_loop.clone().get(0).unwrap().data.len() / VALUES_PER_PIXEL) as u16
     }
-}
\ No newline at end of file
+}
+
+impl FrameSource for Animation {
+    ///
+    /// Returns the in transition (if any) followed by the loop, repeated indefinitely.
+    fn get_frame(&self, tick: usize, _elapsed: f64) -> Cow<'_, AnimationFrame> {
+        let frames_in_len = self.frames_in.as_ref().map_or(0, |frames| frames.len());
+
+        match &self.frames_in {
+            Some(frames_in) if tick < frames_in_len => Cow::Borrowed(&frames_in[tick]),
+            _ => Cow::Borrowed(&self.frames_loop[(tick - frames_in_len) % self.frames_loop.len()]),
+        }
+    }
+
+    fn get_frame_out(&self, tick: usize, _elapsed: f64) -> Option<Cow<'_, AnimationFrame>> {
+        self.frames_out.as_ref().and_then(|frames| frames.get(tick)).map(Cow::Borrowed)
+    }
+
+    fn settle(self: Box<Self>, _elapsed: f64) -> Box<dyn FrameSource> {
+        self
+    }
+}
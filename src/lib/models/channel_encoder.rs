@@ -0,0 +1,156 @@
+use std::borrow::Cow;
+use crate::lib::models::frame::{AnimationFrame, VALUES_PER_PIXEL};
+use crate::settings::{ChannelLayout, Target, WhiteMode, DEFAULT_COOL_WHITE_TEMPERATURE, DEFAULT_WARM_WHITE_TEMPERATURE, DEFAULT_WHITE_TEMPERATURE};
+use crate::utils::color::temperature_to_rgb;
+
+/// Encodes RGB frames into the channel layout of a target (e.g. GRB or RGBW)
+///
+/// Frames are composed in RGB, the encoder is applied right before a frame is encoded for the target.
+///
+/// `layout` - channels of each LED, in the order they are sent
+/// `mode` - how the white channel(s) are extracted, RGBW/RGBWW only
+/// `whites` - color of each white LED (brightest channel 1.0), RGBW/RGBWW only
+pub struct ChannelEncoder {
+    layout: ChannelLayout,
+    mode: WhiteMode,
+    whites: Vec<[f64; 3]>,
+}
+
+impl ChannelEncoder {
+    ///
+    /// Returns:
+    ///     the channel encoder of the target, or None if the target is RGB (i.e. frames are sent as is)
+    pub fn new(device: &Target) -> Option<Self> {
+        let white = device.white.clone().unwrap_or_default();
+
+        let whites = match device.channels {
+            ChannelLayout::Rgb => return None,
+            ChannelLayout::Grb | ChannelLayout::Bgr => Vec::new(),
+            ChannelLayout::Rgbw => vec![temperature_to_rgb(white.temperature.unwrap_or(DEFAULT_WHITE_TEMPERATURE))],
+            ChannelLayout::Rgbww => vec![
+                temperature_to_rgb(white.warm_temperature.unwrap_or(DEFAULT_WARM_WHITE_TEMPERATURE)),
+                temperature_to_rgb(white.cool_temperature.unwrap_or(DEFAULT_COOL_WHITE_TEMPERATURE)),
+            ],
+        };
+
+        Some(Self { layout: device.channels, mode: white.mode, whites })
+    }
+
+    /// Encodes each RGB pixel of the frame into the channel layout
+    pub fn apply<'a>(&self, frame: &'a AnimationFrame) -> Cow<'a, AnimationFrame> {
+        let data = frame.data.chunks_exact(VALUES_PER_PIXEL).flat_map(|pixel| {
+            let (r, g, b) = (pixel[0], pixel[1], pixel[2]);
+
+            match self.layout {
+                ChannelLayout::Rgb => vec![r, g, b],
+                ChannelLayout::Grb => vec![g, r, b],
+                ChannelLayout::Bgr => vec![b, g, r],
+                ChannelLayout::Rgbw | ChannelLayout::Rgbww => self.extract_white([r, g, b]),
+            }
+        }).collect();
+
+        Cow::Owned(AnimationFrame { data })
+    }
+
+    ///
+    /// Moves the white part of the color to the white channel(s).
+    ///
+    /// Returns:
+    ///     the remaining red, green and blue, then each white channel
+    fn extract_white(&self, color: [u8; 3]) -> Vec<u8> {
+        let mut rgb = color.map(|value| value as f64);
+
+        let whites: Vec<f64> = match self.mode {
+            // the whites are assumed to add up to neutral white, so they share the common part equally
+            WhiteMode::MinSubtract => {
+                let white = rgb.iter().copied().fold(f64::MAX, f64::min);
+                rgb = rgb.map(|value| value - white);
+                vec![white / self.whites.len() as f64; self.whites.len()]
+            },
+            // the white covering most of the color is extracted first, then the other one from the remainder
+            WhiteMode::ColorTemperature => {
+                let mut amounts = vec![0.0; self.whites.len()];
+                let mut order: Vec<usize> = (0..self.whites.len()).collect();
+                let extracted = |i: usize| white_amount(rgb, self.whites[i]) * self.whites[i].iter().sum::<f64>();
+                order.sort_by(|a, b| extracted(*b).total_cmp(&extracted(*a)));
+
+                for i in order {
+                    amounts[i] = white_amount(rgb, self.whites[i]);
+                    rgb = [0, 1, 2].map(|channel| (rgb[channel] - amounts[i] * self.whites[i][channel]).max(0.0));
+                }
+
+                amounts
+            },
+        };
+
+        rgb.iter().chain(whites.iter()).map(|value| value.round().clamp(0.0, 255.0) as u8).collect()
+    }
+}
+
+/// Largest amount of the white LED (0 - 255) contained in the color,
+/// channels the white LED doesn't emit (e.g. the blue of a very warm white) don't limit it
+fn white_amount(color: [f64; 3], white: [f64; 3]) -> f64 {
+    (0..3).filter(|channel| white[*channel] > 0.0)
+        .map(|channel| color[channel] / white[channel]).fold(f64::MAX, f64::min).min(255.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encoder(layout: ChannelLayout, mode: WhiteMode, whites: Vec<[f64; 3]>) -> ChannelEncoder {
+        ChannelEncoder { layout, mode, whites }
+    }
+
+    // warm white without blue, and a bluish cool white
+    static WARM: [f64; 3] = [1.0, 0.5, 0.0];
+    static COOL: [f64; 3] = [0.8, 0.9, 1.0];
+
+    #[test]
+    fn reorders_channels() {
+        let frame = AnimationFrame { data: vec![1, 2, 3] };
+
+        assert_eq!(encoder(ChannelLayout::Grb, WhiteMode::MinSubtract, Vec::new()).apply(&frame).data, [2, 1, 3]);
+        assert_eq!(encoder(ChannelLayout::Bgr, WhiteMode::MinSubtract, Vec::new()).apply(&frame).data, [3, 2, 1]);
+    }
+
+    #[test]
+    fn min_subtract_moves_common_white() {
+        let rgbw = encoder(ChannelLayout::Rgbw, WhiteMode::MinSubtract, vec![WARM]);
+        assert_eq!(rgbw.extract_white([200, 150, 100]), [100, 50, 0, 100]);
+
+        // split equally between the whites
+        let rgbww = encoder(ChannelLayout::Rgbww, WhiteMode::MinSubtract, vec![WARM, COOL]);
+        assert_eq!(rgbww.extract_white([200, 150, 100]), [100, 50, 0, 50, 50]);
+    }
+
+    #[test]
+    fn extracts_white_of_led_color() {
+        let rgbw = encoder(ChannelLayout::Rgbw, WhiteMode::ColorTemperature, vec![[1.0, 0.8, 0.6]]);
+
+        assert_eq!(rgbw.extract_white([255, 204, 153]), [0, 0, 0, 255]);
+        // limited by red, the remaining green and blue stay on the color channels
+        assert_eq!(rgbw.extract_white([200, 200, 200]), [0, 40, 80, 200]);
+        assert_eq!(rgbw.extract_white([0, 100, 100]), [0, 100, 100, 0]);
+    }
+
+    #[test]
+    fn extracts_warm_white_without_blue() {
+        let rgbw = encoder(ChannelLayout::Rgbw, WhiteMode::ColorTemperature, vec![WARM]);
+
+        assert_eq!(rgbw.extract_white([200, 100, 0]), [0, 0, 0, 200]);
+        assert_eq!(rgbw.extract_white([200, 100, 40]), [0, 0, 40, 200]);
+        assert_eq!(rgbw.extract_white([200, 60, 0]), [80, 0, 0, 120]);
+    }
+
+    #[test]
+    fn extracts_white_covering_most_of_color_first() {
+        let rgbww = encoder(ChannelLayout::Rgbww, WhiteMode::ColorTemperature, vec![WARM, COOL]);
+
+        assert_eq!(rgbww.extract_white([200, 100, 0]), [0, 0, 0, 200, 0]);
+        // the warm white alone would be 160 (leaving [0, 100, 200]), but the cool white covers more of the color
+        assert_eq!(rgbww.extract_white([160, 180, 200]), [0, 0, 0, 0, 200]);
+        // warm (140 * 1.5 = 210) before cool (50 * 2.7 = 135), leaving [0, 25, 50] without red for the cool white
+        assert_eq!(rgbww.extract_white([140, 95, 50]), [0, 25, 50, 140, 0]);
+    }
+}
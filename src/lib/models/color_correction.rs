@@ -0,0 +1,169 @@
+use std::borrow::Cow;
+use crate::lib::models::frame::{AnimationFrame, VALUES_PER_PIXEL};
+use crate::settings::{Calibration, Target, LUT_SIZE};
+use crate::utils::color::temperature_to_rgb;
+
+/// Color correction of a target (gamma or LUT, white balance, color temperature and minimum brightness)
+///
+/// Applied to the RGB frames after all effects, right before they are encoded for the target.
+/// The curves keep fractional output values, which temporal dithering spreads over consecutive frames,
+/// so dim fades (e.g. the low end of the breathing effect) don't visibly step between output values.
+///
+/// `curves` - for each channel, the output value (0.0 - 255.0) of each input value
+/// `dither` - whether to dither over time
+/// `errors` - rounding error carried over to the next frame, for each channel of the frame;
+///     the color correction is created for each animation, so the errors start from zero when the animation changes
+pub struct ColorCorrection {
+    curves: [Vec<f64>; 3],
+    dither: bool,
+    errors: Vec<f64>,
+}
+
+impl ColorCorrection {
+    ///
+    /// Returns:
+    ///     the color correction of the target, or None if the target isn't calibrated (i.e. frames are sent as is)
+    pub fn new(device: &Target) -> Option<Self> {
+        device.calibration.as_ref().map(Self::from_calibration)
+    }
+
+    fn from_calibration(calibration: &Calibration) -> Self {
+        let gamma = calibration.gamma.unwrap_or(1.0);
+        let gains = calibration.gains.unwrap_or([1.0, 1.0, 1.0]);
+        let temperature = calibration.temperature.map_or([1.0, 1.0, 1.0], temperature_to_rgb);
+        let min_brightness = calibration.min_brightness.unwrap_or(0) as f64;
+
+        let curves = [0, 1, 2].map(|channel| {
+            (0..LUT_SIZE).map(|value| {
+                let brightness = match &calibration.lut {
+                    Some(lut) => lut[value] as f64 / 255.0,
+                    None => (value as f64 / 255.0).powf(gamma),
+                };
+                let output = brightness * gains[channel] * temperature[channel] * 255.0;
+
+                // channels that are on stay at least at the minimum brightness, unless turned off by their gain
+                match value > 0 && output > 0.0 {
+                    true => output.max(min_brightness),
+                    false => output,
+                }
+            }).collect()
+        });
+
+        Self { curves, dither: calibration.dither.unwrap_or(false), errors: Vec::new() }
+    }
+
+    /// Corrects each channel of the frame, carrying the rounding errors over to the next frame if dithering
+    pub fn apply<'a>(&mut self, frame: &'a AnimationFrame) -> Cow<'a, AnimationFrame> {
+        // e.g. 1-D targets switching between a single color and a color per LED
+        if self.errors.len() != frame.data.len() {
+            self.errors = vec![0.0; frame.data.len()];
+        }
+
+        let (curves, errors, dither) = (&self.curves, &mut self.errors, self.dither);
+
+        let data = frame.data.iter().zip(errors.iter_mut()).enumerate().map(|(i, (value, error))| {
+            let output = curves[i % VALUES_PER_PIXEL][*value as usize];
+
+            match dither {
+                true => {
+                    let dithered = (output + *error).round().clamp(0.0, 255.0);
+                    *error += output - dithered;
+                    dithered as u8
+                },
+                false => output.round() as u8,
+            }
+        }).collect();
+
+        Cow::Owned(AnimationFrame { data })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(correction: &mut ColorCorrection, data: Vec<u8>) -> Vec<u8> {
+        correction.apply(&AnimationFrame { data }).into_owned().data
+    }
+
+    #[test]
+    fn gamma_one_is_identity() {
+        let mut correction = ColorCorrection::from_calibration(&Calibration { gamma: Some(1.0), ..Default::default() });
+        let data: Vec<u8> = (0..=255).collect();
+
+        assert_eq!(apply(&mut correction, data.clone()), data);
+    }
+
+    #[test]
+    fn applies_gamma() {
+        let mut correction = ColorCorrection::from_calibration(&Calibration { gamma: Some(2.2), ..Default::default() });
+
+        // (128 / 255)^2.2 * 255 = 55.98
+        assert_eq!(apply(&mut correction, vec![0, 128, 255]), [0, 56, 255]);
+    }
+
+    #[test]
+    fn applies_lut() {
+        let lut: Vec<u8> = (0..=255).rev().collect();
+        let mut correction = ColorCorrection::from_calibration(&Calibration { lut: Some(lut.clone()), ..Default::default() });
+
+        assert_eq!(apply(&mut correction, (0..=255).collect()), lut);
+    }
+
+    #[test]
+    fn scales_channels_by_gains() {
+        let mut correction = ColorCorrection::from_calibration(&Calibration { gains: Some([1.0, 0.5, 0.25]), ..Default::default() });
+
+        assert_eq!(apply(&mut correction, vec![200, 200, 200, 255, 255, 255]), [200, 100, 50, 255, 128, 64]);
+    }
+
+    #[test]
+    fn scales_channels_by_temperature() {
+        let temperature = temperature_to_rgb(3000);
+        let mut correction = ColorCorrection::from_calibration(&Calibration { temperature: Some(3000), ..Default::default() });
+
+        assert_eq!(apply(&mut correction, vec![255, 255, 255]), temperature.map(|value| (value * 255.0).round() as u8));
+
+        let mut correction = ColorCorrection::from_calibration(&Calibration { temperature: Some(3000), gains: Some([1.0, 0.9, 0.8]), ..Default::default() });
+        assert_eq!(apply(&mut correction, vec![255, 255, 255]), [255, 159, 88]);
+    }
+
+    #[test]
+    fn min_brightness_lifts_only_channels_that_are_on() {
+        let calibration = Calibration { gamma: Some(2.2), gains: Some([1.0, 1.0, 0.0]), min_brightness: Some(10), ..Default::default() };
+        let mut correction = ColorCorrection::from_calibration(&calibration);
+
+        assert_eq!(apply(&mut correction, vec![0, 0, 0]), [0, 0, 0]);
+        // blue is turned off by its gain
+        assert_eq!(apply(&mut correction, vec![1, 1, 1]), [10, 10, 0]);
+        assert_eq!(apply(&mut correction, vec![255, 255, 255]), [255, 255, 0]);
+    }
+
+    #[test]
+    fn doesnt_dither_by_default() {
+        let mut correction = ColorCorrection::from_calibration(&Calibration { gamma: Some(2.2), ..Default::default() });
+
+        // (64 / 255)^2.2 * 255 = 12.3
+        for _ in 0..10 {
+            assert_eq!(apply(&mut correction, vec![64, 64, 64]), [12, 12, 12]);
+        }
+    }
+
+    #[test]
+    fn dither_averages_to_the_fractional_value() {
+        let frames = 1000;
+        let mut correction = ColorCorrection::from_calibration(&Calibration { gamma: Some(2.2), dither: Some(true), ..Default::default() });
+        let target = correction.curves[1][64];
+
+        let mut sum = 0.0;
+        for _ in 0..frames {
+            let data = apply(&mut correction, vec![0, 64, 255]);
+
+            assert_eq!((data[0], data[2]), (0, 255));
+            sum += data[1] as f64;
+        }
+
+        // the error carried over is at most half an output value
+        assert!((sum / frames as f64 - target).abs() <= 0.5 / frames as f64 + f64::EPSILON, "expected {}, got {}", target, sum / frames as f64);
+    }
+}
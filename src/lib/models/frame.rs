@@ -1,3 +1,6 @@
+// values of each pixel of a frame (red, green and blue), frames are only encoded into the channels of a target when sent
+pub static VALUES_PER_PIXEL: usize = 3;
+
 #[derive(Clone)]
 pub struct AnimationFrame {
     pub data: Vec<u8>,
@@ -0,0 +1,57 @@
+use std::thread;
+use std::time::{Duration, Instant};
+use log::debug;
+
+/// Clock ticking at absolute deadlines (`start + tick * interval`)
+///
+/// As deadlines don't depend on when the previous frame was sent,
+/// the time spent encoding and sending frames doesn't accumulate as drift.
+/// If a deadline is missed by one or more intervals, the missed ticks are skipped
+/// to stay in sync with the wall clock.
+///
+/// `start` - time of the first tick
+/// `interval` - time between ticks
+/// `tick` - number of the next tick
+/// `missed` - number of ticks skipped due to missed deadlines
+pub struct FrameClock {
+    start: Instant,
+    interval: Duration,
+    tick: u64,
+    missed: u64,
+}
+
+impl FrameClock {
+    pub fn new(interval: Duration) -> Self {
+        Self { start: Instant::now(), interval, tick: 0, missed: 0 }
+    }
+
+    ///
+    /// Waits until the deadline of the next tick.
+    ///
+    /// Returns:
+    ///     the number of the tick, which may skip ahead if deadlines were missed
+    pub fn tick(&mut self) -> u64 {
+        let deadline = self.start + self.interval.mul_f64(self.tick as f64);
+        let now = Instant::now();
+
+        if now < deadline {
+            thread::sleep(deadline - now);
+        } else {
+            let late_ticks = ((now - deadline).as_nanos() / self.interval.as_nanos().max(1)) as u64;
+
+            if late_ticks > 0 {
+                debug!("Missed {} frame deadline(s) at tick {}, skipping ahead", late_ticks, self.tick);
+                self.missed += late_ticks;
+                self.tick += late_ticks;
+            }
+        }
+
+        self.tick += 1;
+        self.tick - 1
+    }
+
+    /// Total number of ticks skipped due to missed deadlines
+    pub fn missed(&self) -> u64 {
+        self.missed
+    }
+}
@@ -0,0 +1,37 @@
+use std::borrow::Cow;
+use crate::settings::Target;
+use super::frame::AnimationFrame;
+
+/// Source of the frames of an animation, computing each frame on demand
+///
+/// Frames are requested by tick and by time since the start of the animation,
+/// so sources can be either precomputed (see `Animation`) or computed from the time (see `StreamedAnimation`).
+pub trait FrameSource: Send {
+    ///
+    /// Returns the frame to be shown at `tick`, `elapsed` seconds since the start of the animation
+    fn get_frame(&self, tick: usize, elapsed: f64) -> Cow<'_, AnimationFrame>;
+
+    ///
+    /// Returns the frame of the out transition to be shown at `tick`, `elapsed` seconds after the animation stopped.
+    ///
+    /// Returns None once the out transition is finished, or if there is none.
+    fn get_frame_out(&self, _tick: usize, _elapsed: f64) -> Option<Cow<'_, AnimationFrame>> {
+        None
+    }
+
+    ///
+    /// Returns the source to keep playing from `elapsed` seconds on,
+    /// dropping anything that is no longer needed (e.g. the outgoing animation of a finished transition)
+    fn settle(self: Box<Self>, elapsed: f64) -> Box<dyn FrameSource>;
+}
+
+/// Frame source to be played on a target device
+///
+/// `target` - resolved IP address of the target device, without port
+/// `device` - the target settings
+/// `source` - the frames to be played
+pub struct TargetAnimation {
+    pub target: String,
+    pub device: Target,
+    pub source: Box<dyn FrameSource>,
+}
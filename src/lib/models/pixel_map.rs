@@ -0,0 +1,233 @@
+use std::borrow::Cow;
+use crate::lib::models::frame::{AnimationFrame, VALUES_PER_PIXEL};
+use crate::settings::{Corner, Mapping, Mirror, Target, WiringDirection};
+
+/// Reorders frames from row-major order into the order the LEDs of a matrix are physically wired in
+///
+/// Frames are composed in row-major order, with the first pixel at the top left;
+/// the map is applied right before a frame is encoded for the target.
+///
+/// `indices` - for each LED in wiring order, the index of its pixel in the row-major frame
+pub struct PixelMap {
+    indices: Vec<usize>,
+}
+
+impl PixelMap {
+    ///
+    /// Builds the pixel map of the target from its `mapping` settings.
+    ///
+    /// Returns:
+    ///     the pixel map, or None if the target is 1-D or has no mapping (i.e. is wired in row-major order)
+    pub fn new(device: &Target) -> Option<Self> {
+        match (device.size(), &device.mapping) {
+            ((width, height), Some(mapping)) if height > 0 => {
+                Some(Self::from_mapping(mapping, width as usize, height as usize))
+            },
+            _ => None,
+        }
+    }
+
+    ///
+    /// `mapping` - the wiring of the matrix, already validated against its size
+    /// `width`, `height` - size of the frames (i.e. before rotation)
+    fn from_mapping(mapping: &Mapping, width: usize, height: usize) -> Self {
+        let rotation = mapping.rotation.unwrap_or(0);
+
+        // the image is rotated first, so the panels are laid out on the rotated size
+        let (wired_width, wired_height) = match rotation {
+            90 | 270 => (height, width),
+            _ => (width, height),
+        };
+        let (tile_width, tile_height) = mapping.tile_size
+            .map_or((wired_width, wired_height), |(tile_width, tile_height)| (tile_width as usize, tile_height as usize));
+        let tiles_x = wired_width / tile_width;
+        let tile_pixels = tile_width * tile_height;
+
+        let indices = (0..width * height).map(|led| {
+            let chain_position = led / tile_pixels;
+            let tile = mapping.tile_order.as_ref().map_or(chain_position, |tile_order| tile_order[chain_position] as usize);
+
+            let (x, y) = wiring_position(led % tile_pixels, tile_width, tile_height, mapping.start, mapping.direction, mapping.serpentine.unwrap_or(false));
+            let (x, y) = ((tile % tiles_x) * tile_width + x, (tile / tiles_x) * tile_height + y);
+
+            let (x, y) = match mapping.mirror {
+                Mirror::None => (x, y),
+                Mirror::Horizontal => (wired_width - 1 - x, y),
+                Mirror::Vertical => (x, wired_height - 1 - y),
+                Mirror::Both => (wired_width - 1 - x, wired_height - 1 - y),
+            };
+
+            // position in the rotated image, back to the position in the frame
+            let (x, y) = match rotation {
+                90 => (y, height - 1 - x),
+                180 => (width - 1 - x, height - 1 - y),
+                270 => (width - 1 - y, x),
+                _ => (x, y),
+            };
+
+            y * width + x
+        }).collect();
+
+        Self { indices }
+    }
+
+    ///
+    /// Reorders the frame into wiring order.
+    ///
+    /// Frames not covering the entire matrix (e.g. a single color) are returned as is.
+    pub fn apply<'a>(&self, frame: &'a AnimationFrame) -> Cow<'a, AnimationFrame> {
+        if frame.data.len() != self.indices.len() * VALUES_PER_PIXEL {
+            return Cow::Borrowed(frame);
+        }
+
+        let data = self.indices.iter()
+            .flat_map(|index| &frame.data[index * VALUES_PER_PIXEL..(index + 1) * VALUES_PER_PIXEL])
+            .copied()
+            .collect();
+
+        Cow::Owned(AnimationFrame { data })
+    }
+}
+
+///
+/// Position of the `index`-th LED within a panel of `width` x `height`.
+///
+/// `start` - corner of the first LED
+/// `direction` - whether LEDs run along rows or columns
+/// `serpentine` - whether every other row (or column) runs backwards
+fn wiring_position(index: usize, width: usize, height: usize, start: Corner, direction: WiringDirection, serpentine: bool) -> (usize, usize) {
+    // position along the lines (rows or columns) starting at the top left, then flipped to the start corner
+    let line_length = match direction {
+        WiringDirection::Horizontal => width,
+        WiringDirection::Vertical => height,
+    };
+    let (line, along) = (index / line_length, index % line_length);
+    let along = match serpentine && line % 2 == 1 {
+        true => line_length - 1 - along,
+        false => along,
+    };
+
+    let (x, y) = match direction {
+        WiringDirection::Horizontal => (along, line),
+        WiringDirection::Vertical => (line, along),
+    };
+
+    match start {
+        Corner::TopLeft => (x, y),
+        Corner::TopRight => (width - 1 - x, y),
+        Corner::BottomLeft => (x, height - 1 - y),
+        Corner::BottomRight => (width - 1 - x, height - 1 - y),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::settings::tests::target_from_toml;
+    use super::*;
+
+    // frames are 4 x 2, so the index of each pixel in the row-major frame is:
+    // 0 1 2 3
+    // 4 5 6 7
+    static WIDTH: usize = 4;
+    static HEIGHT: usize = 2;
+
+    fn indices(mapping: Mapping) -> Vec<usize> {
+        PixelMap::from_mapping(&mapping, WIDTH, HEIGHT).indices
+    }
+
+    fn wiring(start: Corner, direction: WiringDirection, serpentine: bool) -> Vec<usize> {
+        indices(Mapping { start, direction, serpentine: Some(serpentine), ..Default::default() })
+    }
+
+    #[test]
+    fn wires_rows_progressive() {
+        assert_eq!(wiring(Corner::TopLeft, WiringDirection::Horizontal, false), [0, 1, 2, 3, 4, 5, 6, 7]);
+        assert_eq!(wiring(Corner::TopRight, WiringDirection::Horizontal, false), [3, 2, 1, 0, 7, 6, 5, 4]);
+        assert_eq!(wiring(Corner::BottomLeft, WiringDirection::Horizontal, false), [4, 5, 6, 7, 0, 1, 2, 3]);
+        assert_eq!(wiring(Corner::BottomRight, WiringDirection::Horizontal, false), [7, 6, 5, 4, 3, 2, 1, 0]);
+    }
+
+    #[test]
+    fn wires_rows_serpentine() {
+        assert_eq!(wiring(Corner::TopLeft, WiringDirection::Horizontal, true), [0, 1, 2, 3, 7, 6, 5, 4]);
+        assert_eq!(wiring(Corner::TopRight, WiringDirection::Horizontal, true), [3, 2, 1, 0, 4, 5, 6, 7]);
+        assert_eq!(wiring(Corner::BottomLeft, WiringDirection::Horizontal, true), [4, 5, 6, 7, 3, 2, 1, 0]);
+        assert_eq!(wiring(Corner::BottomRight, WiringDirection::Horizontal, true), [7, 6, 5, 4, 0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn wires_columns_progressive() {
+        assert_eq!(wiring(Corner::TopLeft, WiringDirection::Vertical, false), [0, 4, 1, 5, 2, 6, 3, 7]);
+        assert_eq!(wiring(Corner::TopRight, WiringDirection::Vertical, false), [3, 7, 2, 6, 1, 5, 0, 4]);
+        assert_eq!(wiring(Corner::BottomLeft, WiringDirection::Vertical, false), [4, 0, 5, 1, 6, 2, 7, 3]);
+        assert_eq!(wiring(Corner::BottomRight, WiringDirection::Vertical, false), [7, 3, 6, 2, 5, 1, 4, 0]);
+    }
+
+    #[test]
+    fn wires_columns_serpentine() {
+        assert_eq!(wiring(Corner::TopLeft, WiringDirection::Vertical, true), [0, 4, 5, 1, 2, 6, 7, 3]);
+        assert_eq!(wiring(Corner::TopRight, WiringDirection::Vertical, true), [3, 7, 6, 2, 1, 5, 4, 0]);
+        assert_eq!(wiring(Corner::BottomLeft, WiringDirection::Vertical, true), [4, 0, 1, 5, 6, 2, 3, 7]);
+        assert_eq!(wiring(Corner::BottomRight, WiringDirection::Vertical, true), [7, 3, 2, 6, 5, 1, 0, 4]);
+    }
+
+    #[test]
+    fn rotates() {
+        let rotated = |rotation| indices(Mapping { rotation: Some(rotation), ..Default::default() });
+
+        assert_eq!(rotated(0), [0, 1, 2, 3, 4, 5, 6, 7]);
+        // the rotated image is 2 x 4, wired row by row
+        assert_eq!(rotated(90), [4, 0, 5, 1, 6, 2, 7, 3]);
+        assert_eq!(rotated(180), [7, 6, 5, 4, 3, 2, 1, 0]);
+        assert_eq!(rotated(270), [3, 7, 2, 6, 1, 5, 0, 4]);
+    }
+
+    #[test]
+    fn mirrors() {
+        let mirrored = |mirror| indices(Mapping { mirror, ..Default::default() });
+
+        assert_eq!(mirrored(Mirror::Horizontal), [3, 2, 1, 0, 7, 6, 5, 4]);
+        assert_eq!(mirrored(Mirror::Vertical), [4, 5, 6, 7, 0, 1, 2, 3]);
+        assert_eq!(mirrored(Mirror::Both), [7, 6, 5, 4, 3, 2, 1, 0]);
+    }
+
+    #[test]
+    fn mirrors_after_rotation() {
+        // rotated 90 degrees, then the left and right of the 2 x 4 image swapped
+        assert_eq!(indices(Mapping { rotation: Some(90), mirror: Mirror::Horizontal, ..Default::default() }), [0, 4, 1, 5, 2, 6, 3, 7]);
+    }
+
+    #[test]
+    fn tiles() {
+        assert_eq!(indices(Mapping { tile_size: Some((2, 2)), ..Default::default() }), [0, 1, 4, 5, 2, 3, 6, 7]);
+        assert_eq!(indices(Mapping { tile_size: Some((2, 2)), tile_order: Some(vec![1, 0]), ..Default::default() }), [2, 3, 6, 7, 0, 1, 4, 5]);
+        assert_eq!(
+            indices(Mapping { tile_size: Some((2, 2)), tile_order: Some(vec![1, 0]), serpentine: Some(true), ..Default::default() }),
+            [2, 3, 7, 6, 0, 1, 5, 4]
+        );
+    }
+
+    #[test]
+    fn reorders_frames() {
+        let map = PixelMap::from_mapping(&Mapping { serpentine: Some(true), ..Default::default() }, WIDTH, HEIGHT);
+        let frame = AnimationFrame { data: (0..(WIDTH * HEIGHT * VALUES_PER_PIXEL) as u8).collect() };
+
+        let pixels: Vec<u8> = map.apply(&frame).data.chunks_exact(VALUES_PER_PIXEL).map(|pixel| pixel[0] / VALUES_PER_PIXEL as u8).collect();
+        assert_eq!(pixels, [0, 1, 2, 3, 7, 6, 5, 4]);
+    }
+
+    #[test]
+    fn passes_through_frames_not_covering_the_matrix() {
+        let map = PixelMap::from_mapping(&Mapping { serpentine: Some(true), ..Default::default() }, WIDTH, HEIGHT);
+        let frame = AnimationFrame { data: vec![255, 0, 0] };
+
+        assert!(matches!(map.apply(&frame), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn is_none_without_mapping_or_for_strips() {
+        assert!(PixelMap::new(&target_from_toml("host = \"matrix\"\nsize = [4, 2]")).is_none());
+        assert!(PixelMap::new(&target_from_toml("host = \"strip\"\nsize = [8, 0]\n[mapping]\nserpentine = true")).is_none());
+        assert!(PixelMap::new(&target_from_toml("host = \"matrix\"\nsize = [4, 2]\n[mapping]\nserpentine = true")).is_some());
+    }
+}
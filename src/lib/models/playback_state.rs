@@ -1,16 +1,35 @@
-use rspotify::model::{AudioFeatures, CurrentPlaybackContext, Id, PlayableItem};
+use std::sync::Arc;
+use std::time::Instant;
+use rspotify::model::{AudioAnalysis, AudioFeatures, CurrentPlaybackContext, Id, PlayableItem, RepeatState};
 use crate::settings::SETTINGS;
 
+// difference between the reported and interpolated position in the track, above which the track is considered seeked
+static SEEK_THRESHOLD_MS: i64 = 1500;
+
 /// State of the current playback, to be tracked
+///
+/// `progress_ms` - position in the track when the state was observed
+/// `duration_ms` - duration of the track
+/// `observed_at` - when the state was fetched from Spotify
+/// `shuffle` - whether shuffle is on
+/// `repeat` - repeat mode (off, track or context)
 #[derive(Debug, Clone)]
 pub struct PlaybackState {
     pub is_playing: bool,
     pub track_name: Option<String>,
+    pub artist_name: Option<String>,
     pub track_id: Option<String>,
     pub cover_url: Option<String>,
     pub features: Option<AudioFeatures>,
+    pub analysis: Option<Arc<AudioAnalysis>>,
+    pub progress_ms: Option<u64>,
+    pub duration_ms: Option<u64>,
+    pub observed_at: Instant,
+    pub shuffle: bool,
+    pub repeat: RepeatState,
 }
 
+/// Progress is intentionally ignored, as it changes on every poll; use `is_seeked` to detect jumps in the position
 impl PartialEq for PlaybackState {
     fn eq(&self, other: &Self) -> bool {
         self.is_playing == other.is_playing &&
@@ -28,9 +47,16 @@ impl PlaybackState {
                 Self {
                     is_playing: context.is_playing,
                     track_name: Some(String::from(track.name)),
+                    artist_name: Some(track.artists.iter().map(|artist| artist.name.as_str()).collect::<Vec<&str>>().join(", ")),
                     track_id: Some(String::from(track.id.unwrap().id())),
                     cover_url: Some(track.album.images.first().unwrap().url.clone()),
                     features: None,
+                    analysis: None,
+                    progress_ms: context.progress.map(|progress| progress.num_milliseconds().max(0) as u64),
+                    duration_ms: Some(track.duration.num_milliseconds().max(0) as u64),
+                    observed_at: Instant::now(),
+                    shuffle: context.shuffle_state,
+                    repeat: context.repeat_state,
                     }
             },
             Some(PlayableItem::Episode(_)) => PlaybackState::none(),
@@ -42,13 +68,170 @@ impl PlaybackState {
         self.features = features;
     }
 
+    pub fn add_analysis(&mut self, analysis: Option<Arc<AudioAnalysis>>) {
+        self.analysis = analysis;
+    }
+
     pub fn none() -> Self {
         Self {
                 is_playing: false,
                 track_name: None,
+                artist_name: None,
                 track_id: None,
                 cover_url: SETTINGS.read().unwrap().app.idle_image_url.clone(),
                 features: None,
+                analysis: None,
+                progress_ms: None,
+                duration_ms: None,
+                observed_at: Instant::now(),
+                shuffle: false,
+                repeat: RepeatState::Off,
             }
     }
-}
\ No newline at end of file
+
+    ///
+    /// Returns the position in the track right now, interpolated from the last observed position
+    pub fn position_ms(&self) -> Option<u64> {
+        self.position_ms_at(Instant::now())
+    }
+
+    ///
+    /// Returns the position in the track at `at`, interpolated from the last observed position.
+    ///
+    /// The position only advances while playing, and stops at the end of the track.
+    pub fn position_ms_at(&self, at: Instant) -> Option<u64> {
+        let elapsed = match self.is_playing {
+            true => at.saturating_duration_since(self.observed_at).as_millis() as u64,
+            false => 0,
+        };
+        let position = self.progress_ms? + elapsed;
+
+        Some(self.duration_ms.map_or(position, |duration| position.min(duration)))
+    }
+
+    ///
+    /// Returns the interpolated position as a fraction (0.0 - 1.0) of the track's duration
+    pub fn progress_fraction(&self) -> Option<f64> {
+        match (self.position_ms(), self.duration_ms) {
+            (Some(position), Some(duration)) if duration > 0 => Some(position as f64 / duration as f64),
+            _ => None,
+        }
+    }
+
+    ///
+    /// Whether the same track is still playing, but its position jumped
+    /// from where it was expected to be since `previous` (e.g. seeked, restarted or repeated)
+    pub fn is_seeked(&self, previous: &PlaybackState) -> bool {
+        if self.track_id.is_none() || self.track_id != previous.track_id {
+            return false;
+        }
+
+        match (self.progress_ms, previous.position_ms_at(self.observed_at)) {
+            (Some(progress), Some(expected)) => (progress as i64 - expected as i64).abs() > SEEK_THRESHOLD_MS,
+            _ => false,
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use super::*;
+
+    fn state(is_playing: bool, track_id: Option<&str>, progress_ms: u64, observed_at: Instant) -> PlaybackState {
+        PlaybackState {
+            is_playing,
+            track_name: None,
+            artist_name: None,
+            track_id: track_id.map(String::from),
+            cover_url: None,
+            features: None,
+            analysis: None,
+            progress_ms: Some(progress_ms),
+            duration_ms: Some(10_000),
+            observed_at,
+            shuffle: false,
+            repeat: RepeatState::Off,
+        }
+    }
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn interpolates_position_while_playing() {
+        let now = Instant::now();
+        let playing = state(true, Some("track"), 2_000, now);
+
+        assert_eq!(playing.position_ms_at(now), Some(2_000));
+        assert_eq!(playing.position_ms_at(now + ms(1_500)), Some(3_500));
+        // earlier than observed
+        assert_eq!(playing.position_ms_at(now - ms(500)), Some(2_000));
+    }
+
+    #[test]
+    fn stops_position_at_end_of_track() {
+        let now = Instant::now();
+        let mut playing = state(true, Some("track"), 9_000, now);
+
+        assert_eq!(playing.position_ms_at(now + ms(5_000)), Some(10_000));
+
+        playing.duration_ms = None;
+        assert_eq!(playing.position_ms_at(now + ms(5_000)), Some(14_000));
+    }
+
+    #[test]
+    fn keeps_position_while_paused() {
+        let now = Instant::now();
+        let paused = state(false, Some("track"), 2_000, now);
+
+        assert_eq!(paused.position_ms_at(now + ms(5_000)), Some(2_000));
+    }
+
+    #[test]
+    fn has_no_position_without_progress() {
+        let now = Instant::now();
+        let mut playing = state(true, Some("track"), 0, now);
+        playing.progress_ms = None;
+
+        assert_eq!(playing.position_ms_at(now + ms(1_000)), None);
+    }
+
+    #[test]
+    fn detects_seek_above_threshold() {
+        let now = Instant::now();
+        let previous = state(true, Some("track"), 2_000, now);
+        // expected at 4000 ms two seconds later
+        let later = now + ms(2_000);
+
+        assert!(!state(true, Some("track"), 4_000, later).is_seeked(&previous));
+        assert!(!state(true, Some("track"), 5_500, later).is_seeked(&previous));
+        assert!(!state(true, Some("track"), 2_500, later).is_seeked(&previous));
+        assert!(state(true, Some("track"), 5_501, later).is_seeked(&previous));
+        assert!(state(true, Some("track"), 2_499, later).is_seeked(&previous));
+    }
+
+    #[test]
+    fn detects_seek_from_paused_and_repeated_track() {
+        let now = Instant::now();
+        let later = now + ms(5_000);
+
+        // paused at 2000 ms, so still expected there
+        let paused = state(false, Some("track"), 2_000, now);
+        assert!(!state(true, Some("track"), 2_000, later).is_seeked(&paused));
+        assert!(state(true, Some("track"), 4_000, later).is_seeked(&paused));
+
+        // expected at the end of the track, but restarted
+        let ending = state(true, Some("track"), 9_000, now);
+        assert!(state(true, Some("track"), 500, later).is_seeked(&ending));
+    }
+
+    #[test]
+    fn changed_or_missing_track_is_not_seeked() {
+        let now = Instant::now();
+        let later = now + ms(2_000);
+
+        assert!(!state(true, Some("other"), 9_000, later).is_seeked(&state(true, Some("track"), 0, now)));
+        assert!(!state(true, None, 9_000, later).is_seeked(&state(true, None, 0, now)));
+    }
+}
@@ -0,0 +1,47 @@
+use std::borrow::Cow;
+use std::sync::Arc;
+use crate::lib::artnet::anim::effects::base::overlay::{BlendMode, ImageLayer, LayerSource, Overlay};
+use super::frame::{AnimationFrame, VALUES_PER_PIXEL};
+use super::frame_source::FrameSource;
+
+/// Animation computing each frame on demand from the time and its layers,
+/// instead of precomputing the frames of a full loop
+///
+/// `overlay` - the layers of the animation, the image (or base layer) with the effect multiplied onto it at the bottom
+/// `num_pixels` - number of pixels of each frame
+pub struct StreamedAnimation {
+    overlay: Overlay,
+    num_pixels: usize,
+}
+
+impl StreamedAnimation {
+    pub fn new(image: Vec<u8>, effect: Arc<dyn LayerSource>) -> Self {
+        let num_pixels = image.len() / VALUES_PER_PIXEL;
+        Self::with_base(ImageLayer::new(image), num_pixels, effect)
+    }
+
+    ///
+    /// Creates an animation of `num_pixels` pixels with `effect` multiplied onto an arbitrary `base` layer instead of an image
+    pub fn with_base(base: impl LayerSource + 'static, num_pixels: usize, effect: Arc<dyn LayerSource>) -> Self {
+        let mut overlay = Overlay::new();
+        overlay.add_layer(base, BlendMode::AlphaOver, 1.0);
+        overlay.add_layer(effect, BlendMode::Multiply, 1.0);
+
+        Self { overlay, num_pixels }
+    }
+
+    /// Adds a layer on top of the image and effect, see `Overlay::add_layer`
+    pub fn add_layer(&mut self, source: impl LayerSource + 'static, blend: BlendMode, opacity: f64) {
+        self.overlay.add_layer(source, blend, opacity);
+    }
+}
+
+impl FrameSource for StreamedAnimation {
+    fn get_frame(&self, _tick: usize, elapsed: f64) -> Cow<'_, AnimationFrame> {
+        Cow::Owned(self.overlay.apply(elapsed, self.num_pixels))
+    }
+
+    fn settle(self: Box<Self>, _elapsed: f64) -> Box<dyn FrameSource> {
+        self
+    }
+}
@@ -0,0 +1,83 @@
+use std::borrow::Cow;
+use crate::lib::artnet::anim::effects::spatial_transitions::SpatialTransitionEffects;
+use crate::lib::artnet::anim::effects::transitions::TransitionEffects;
+use crate::settings::TransitionKind;
+use super::frame::{AnimationFrame, VALUES_PER_PIXEL};
+use super::frame_source::FrameSource;
+
+/// Transition from the animation that was playing on a target to the next one
+///
+/// The outgoing animation keeps playing from where it was stopped,
+/// so its effect carries on smoothly while it is blended out.
+///
+/// `from` - the outgoing animation
+/// `from_tick` - tick the outgoing animation was stopped at
+/// `from_elapsed` - time in seconds the outgoing animation was stopped at
+/// `to` - the incoming animation, played on its own once the transition is finished
+/// `kind` - how the animations are blended
+/// `duration` - duration of the transition in seconds
+/// `size` - size of the target, for spatial transitions
+pub struct Transition {
+    from: Box<dyn FrameSource>,
+    from_tick: usize,
+    from_elapsed: f64,
+    to: Box<dyn FrameSource>,
+    kind: TransitionKind,
+    duration: f64,
+    size: (u8, u8),
+}
+
+impl Transition {
+    pub fn new(from: Box<dyn FrameSource>, from_tick: usize, from_elapsed: f64, to: Box<dyn FrameSource>, kind: TransitionKind, duration: f64, size: (u8, u8)) -> Self {
+        Self { from, from_tick, from_elapsed, to, kind, duration, size }
+    }
+}
+
+impl FrameSource for Transition {
+    fn get_frame(&self, tick: usize, elapsed: f64) -> Cow<'_, AnimationFrame> {
+        let to = self.to.get_frame(tick, elapsed);
+
+        if elapsed >= self.duration {
+            return to;
+        }
+
+        let from = self.from.get_frame(self.from_tick + tick, self.from_elapsed + elapsed);
+
+        // e.g. the target size changed, nothing to blend
+        if from.data.len() != to.data.len() {
+            return to;
+        }
+
+        let progress = elapsed / self.duration;
+        let (width, height) = (self.size.0 as usize, self.size.1 as usize);
+
+        // spatial transitions need a 2-D frame covering the entire target, otherwise crossfade
+        let kind = match self.kind.is_spatial() && (height == 0 || to.data.len() != width * height * VALUES_PER_PIXEL) {
+            true => TransitionKind::Crossfade,
+            false => self.kind,
+        };
+
+        Cow::Owned(match kind {
+            TransitionKind::Crossfade => TransitionEffects::crossfade(&from.data, &to.data, progress),
+            TransitionKind::Fade => TransitionEffects::fade_through_black(&from.data, &to.data, progress),
+            TransitionKind::Cut => return to,
+            TransitionKind::WipeHorizontal => SpatialTransitionEffects::wipe_horizontal(&from.data, &to.data, width, height, progress),
+            TransitionKind::WipeVertical => SpatialTransitionEffects::wipe_vertical(&from.data, &to.data, width, height, progress),
+            TransitionKind::Slide => SpatialTransitionEffects::slide(&from.data, &to.data, width, height, progress),
+            TransitionKind::Radial => SpatialTransitionEffects::radial(&from.data, &to.data, width, height, progress),
+            TransitionKind::Dissolve => SpatialTransitionEffects::dissolve(&from.data, &to.data, width, height, progress),
+            TransitionKind::Pixelate => SpatialTransitionEffects::pixelate(&from.data, &to.data, width, height, progress),
+        })
+    }
+
+    fn get_frame_out(&self, tick: usize, elapsed: f64) -> Option<Cow<'_, AnimationFrame>> {
+        self.to.get_frame_out(tick, elapsed)
+    }
+
+    fn settle(self: Box<Self>, elapsed: f64) -> Box<dyn FrameSource> {
+        match elapsed >= self.duration {
+            true => self.to.settle(elapsed),
+            false => self,
+        }
+    }
+}
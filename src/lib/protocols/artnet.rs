@@ -0,0 +1,77 @@
+use std::net::SocketAddr;
+use artnet_protocol::*;
+use crate::lib::models::frame::AnimationFrame;
+use crate::settings::Target;
+use super::protocol::{get_socket_addr, OutputProtocol, Packet};
+use super::universe::UniverseMapping;
+
+static ARTNET_PORT: u16 = 6454;
+static OP_SYNC: u16 = 0x5200;
+
+/// Art-Net output, sending each shard of a frame as an ArtDmx packet
+///
+/// `addr` - address of the target device
+/// `mapping` - mapping of the frame data onto the target's universes
+/// `sync` - whether to send an ArtSync packet after all ArtDmx packets of a frame
+/// `sequence_counter` - for tracking frame sequence,
+///                      all shards within the same frame will have the same sequence number
+pub struct ArtNetProtocol {
+    addr: SocketAddr,
+    mapping: UniverseMapping,
+    sync: bool,
+    sequence_counter: u8,
+}
+
+impl ArtNetProtocol {
+    pub fn new(target: &str, device: &Target) -> Self {
+        Self {
+            addr: get_socket_addr(target, ARTNET_PORT),
+            mapping: UniverseMapping::from_target(device),
+            sync: device.sync.unwrap_or(false),
+            sequence_counter: 0,
+        }
+    }
+
+    fn calculate_sharded_commands(&self, frame: &AnimationFrame) -> Vec<Vec<u8>> {
+        self.mapping.shard(&frame.data).into_iter().map(|(universe, frame_slice)| {
+            let command: ArtCommand = ArtCommand::Output(Output {
+                data: frame_slice.into(), // The data we're sending to the node
+                sequence: self.sequence_counter,
+                port_address: PortAddress::try_from(universe).unwrap(),
+                ..Output::default()
+            });
+
+            command.write_to_buffer().unwrap()
+        }).collect()
+    }
+
+    ///
+    /// ArtSync packet, instructing the node to output all ArtDmx packets received since the last sync.
+    ///
+    /// NOTE: `artnet_protocol` doesn't implement the ArtSync body (protocol version + 2 aux bytes), so it is built here
+    fn sync_command() -> Vec<u8> {
+        let mut command = Vec::from(ARTNET_HEADER.as_slice());
+        command.extend_from_slice(&OP_SYNC.to_le_bytes());
+        command.extend_from_slice(&ARTNET_PROTOCOL_VERSION);
+        command.extend_from_slice(&[0, 0]); // aux1, aux2
+
+        command
+    }
+}
+
+impl OutputProtocol for ArtNetProtocol {
+    fn encode_frame(&mut self, frame: &AnimationFrame) -> Vec<Packet> {
+        let mut packets: Vec<Packet> = self.calculate_sharded_commands(frame)
+            .into_iter()
+            .map(|data| Packet { addr: self.addr, data })
+            .collect();
+
+        if self.sync {
+            packets.push(Packet { addr: self.addr, data: Self::sync_command() });
+        }
+
+        self.sequence_counter = self.sequence_counter.wrapping_add(1);
+
+        packets
+    }
+}
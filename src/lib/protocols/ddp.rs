@@ -0,0 +1,75 @@
+use std::net::SocketAddr;
+use crate::lib::models::frame::AnimationFrame;
+use crate::settings::{ChannelLayout, Target};
+use super::protocol::{get_socket_addr, OutputProtocol, Packet};
+
+static DDP_PORT: u16 = 4048;
+// 480 RGB (or 360 RGBW) pixels, the maximum WLED accepts in a single DDP packet
+static DDP_CHANNELS_PER_PACKET: usize = 1440;
+
+// DDP header constants
+static DDP_FLAGS_VERSION_1: u8 = 0x40;
+static DDP_FLAGS_PUSH: u8 = 0x01;
+static DDP_TYPE_RGB24: u8 = 0x0b;
+static DDP_TYPE_RGBW32: u8 = 0x1b;
+static DDP_ID_DISPLAY: u8 = 0x01;
+static DDP_MAX_SEQUENCE: u8 = 15;
+
+/// Distributed Display Protocol (DDP) output
+///
+/// Frames are split by byte offset instead of universes, and the last packet of each frame
+/// has the PUSH flag set, so the device only displays the frame once it is complete.
+///
+/// `addr` - address of the target device
+/// `data_type` - RGB or RGBW, from the channel layout of the target
+/// `sequence_counter` - for tracking frame sequence, cycles through 1 - 15 (0 means unused)
+pub struct DdpProtocol {
+    addr: SocketAddr,
+    data_type: u8,
+    sequence_counter: u8,
+}
+
+impl DdpProtocol {
+    pub fn new(target: &str, device: &Target) -> Self {
+        Self {
+            addr: get_socket_addr(target, DDP_PORT),
+            data_type: match device.channels {
+                ChannelLayout::Rgbw => DDP_TYPE_RGBW32,
+                _ => DDP_TYPE_RGB24,
+            },
+            sequence_counter: 0,
+        }
+    }
+
+    fn data_packet(&self, offset: usize, data: &[u8], push: bool) -> Vec<u8> {
+        let mut packet = Vec::with_capacity(10 + data.len());
+
+        packet.push(match push {
+            true => DDP_FLAGS_VERSION_1 | DDP_FLAGS_PUSH,
+            false => DDP_FLAGS_VERSION_1,
+        });
+        packet.push(self.sequence_counter);
+        packet.push(self.data_type);
+        packet.push(DDP_ID_DISPLAY);
+        packet.extend_from_slice(&(offset as u32).to_be_bytes());
+        packet.extend_from_slice(&(data.len() as u16).to_be_bytes());
+        packet.extend_from_slice(data);
+
+        packet
+    }
+}
+
+impl OutputProtocol for DdpProtocol {
+    fn encode_frame(&mut self, frame: &AnimationFrame) -> Vec<Packet> {
+        self.sequence_counter = self.sequence_counter % DDP_MAX_SEQUENCE + 1;
+
+        let num_packets = frame.data.chunks(DDP_CHANNELS_PER_PACKET).len();
+
+        frame.data.chunks(DDP_CHANNELS_PER_PACKET).enumerate().map(|(i, data)| {
+            Packet {
+                addr: self.addr,
+                data: self.data_packet(i * DDP_CHANNELS_PER_PACKET, data, i == num_packets - 1),
+            }
+        }).collect()
+    }
+}
@@ -0,0 +1,231 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::net::{IpAddr, SocketAddr, UdpSocket};
+use std::thread;
+use std::time::{Duration, Instant};
+use artnet_protocol::*;
+use log::{debug, trace, warn};
+use serde_derive::Serialize;
+use crate::utils::wled::get_info;
+
+static ARTNET_PORT: u16 = 6454;
+// ArtNet nodes must reply to an ArtPoll within 3 seconds
+pub static DISCOVERY_TIMEOUT: Duration = Duration::from_secs(3);
+static BROADCAST_ADDR: &str = "255.255.255.255";
+static MAX_PORTS: usize = 4;
+
+/// An ArtNet node found on the network, built from its ArtPollReply(s)
+///
+/// `universes` - port addresses of the node's output ports, merged across all of its replies
+/// `size` - size of the device from the WLED JSON API (in the format of `Target.size`), if available
+#[derive(Debug, Clone, Serialize)]
+pub struct DiscoveredNode {
+    pub ip: IpAddr,
+    pub short_name: String,
+    pub long_name: String,
+    pub universes: Vec<u16>,
+    pub size: Option<(u16, u16)>,
+}
+
+impl DiscoveredNode {
+    fn from_poll_reply(ip: IpAddr, reply: &PollReply) -> Self {
+        // bits 14-8 of the port address are in the net switch, bits 7-4 in the sub switch
+        let net = (reply.port_address[0] & 0x7f) as u16;
+        let subnet = (reply.port_address[1] & 0x0f) as u16;
+        let num_ports = (reply.num_ports[1] as usize).min(MAX_PORTS);
+
+        // bits 3-0 of each output port are in the low nibble of SwOut
+        let universes = reply.swout.iter()
+            .take(num_ports)
+            .map(|swout| (net << 8) | (subnet << 4) | (swout & 0x0f) as u16)
+            .collect();
+
+        Self {
+            ip,
+            short_name: null_terminated_string(&reply.short_name),
+            long_name: null_terminated_string(&reply.long_name),
+            universes,
+            size: None,
+        }
+    }
+
+    ///
+    /// Returns a `[[targets]]` block for this node, ready to be pasted into `config.toml`.
+    ///
+    /// The size of the device is not reported by ArtPoll, so it is only filled in if it was found during discovery.
+    pub fn to_target_config(&self) -> String {
+        let start_universe = self.universes.iter().min().copied().unwrap_or(0);
+
+        let size = match self.size {
+            Some((width, height)) => format!("size = [{}, {}]", width, height),
+            None => "# size could not be detected, please fill in the LED matrix size\n# size = [32, 32]".to_string(),
+        };
+
+        format!(
+            "## {} ({}), universes: {:?}\n[[targets]]\nhost = \"{}\"\n{}\nprotocol = \"artnet\"\nnet = {}\nsubnet = {}\nstart_universe = {}\n",
+            self.long_name, self.short_name, self.universes, self.ip, size,
+            start_universe >> 8, (start_universe >> 4) & 0x0f, start_universe & 0x0f,
+        )
+    }
+}
+
+///
+/// Broadcasts an ArtPoll to `broadcast_addr` and collects ArtPollReplies until `timeout` has passed.
+///
+/// Nodes sending multiple replies (e.g. one per group of 4 ports) are merged into a single node.
+/// The size of each node is then queried from the WLED JSON API, for all nodes in parallel.
+///
+/// Returns:
+///     the discovered nodes, ordered by IP address
+pub fn discover(broadcast_addr: SocketAddr, timeout: Duration) -> io::Result<Vec<DiscoveredNode>> {
+    // replies are sent to the ArtNet port, fall back to any port if it is in use
+    let socket = UdpSocket::bind(("0.0.0.0", ARTNET_PORT)).or_else(|_| {
+        warn!("Port {} is in use, ArtNet nodes replying to the ArtNet port will not be discovered", ARTNET_PORT);
+        UdpSocket::bind("0.0.0.0:0")
+    })?;
+    socket.set_broadcast(true)?;
+
+    let poll = ArtCommand::Poll(Poll::default()).write_to_buffer().unwrap();
+    socket.send_to(&poll, broadcast_addr)?;
+    debug!("Sent ArtPoll to {}", broadcast_addr);
+
+    let mut nodes: BTreeMap<IpAddr, DiscoveredNode> = BTreeMap::new();
+    let deadline = Instant::now() + timeout;
+    let mut buffer = [0u8; 1024];
+
+    while let Some(remaining) = deadline.checked_duration_since(Instant::now()).filter(|d| !d.is_zero()) {
+        socket.set_read_timeout(Some(remaining))?;
+
+        let (length, addr) = match socket.recv_from(&mut buffer) {
+            Ok(received) => received,
+            Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => break,
+            Err(e) => return Err(e),
+        };
+
+        match ArtCommand::from_buffer(&buffer[..length]) {
+            Ok(ArtCommand::PollReply(reply)) => {
+                let node = DiscoveredNode::from_poll_reply(addr.ip(), &reply);
+                trace!("Received ArtPollReply from {}: {:?}", addr, node);
+
+                nodes.entry(addr.ip())
+                    .and_modify(|existing| existing.universes.extend(node.universes.iter()))
+                    .or_insert(node);
+            },
+            // most likely our own broadcast ArtPoll
+            Ok(_) => {},
+            Err(e) => trace!("Ignoring invalid ArtNet packet from {}: {:?}", addr, e),
+        }
+    }
+
+    let handles: Vec<thread::JoinHandle<DiscoveredNode>> = nodes.into_values().map(|mut node| {
+        thread::spawn(move || {
+            node.universes.sort();
+            node.universes.dedup();
+
+            node.size = match get_info(&node.ip.to_string()) {
+                Ok(info) => Some(info.size()),
+                Err(e) => {
+                    debug!("Unable to get WLED device info from {}: {}", node.ip, e);
+                    None
+                },
+            };
+            node
+        })
+    }).collect();
+
+    Ok(handles.into_iter().map(|handle| handle.join().unwrap()).collect())
+}
+
+///
+/// Parses the broadcast IP (default: 255.255.255.255) into the address ArtPolls are sent to
+pub fn get_broadcast_addr(broadcast_addr: Option<&str>) -> Option<SocketAddr> {
+    broadcast_addr.unwrap_or(BROADCAST_ADDR).parse::<IpAddr>().ok().map(|ip| SocketAddr::new(ip, ARTNET_PORT))
+}
+
+fn null_terminated_string(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+    use super::*;
+
+    /// Fake ArtNet node on localhost, answering the first ArtPoll it receives with an ArtPollReply
+    fn spawn_fake_node(reply: PollReply) -> (SocketAddr, thread::JoinHandle<()>) {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = socket.local_addr().unwrap();
+
+        let handle = thread::spawn(move || {
+            let mut buffer = [0u8; 1024];
+            socket.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+            let (length, poller) = socket.recv_from(&mut buffer).unwrap();
+
+            assert!(matches!(ArtCommand::from_buffer(&buffer[..length]), Ok(ArtCommand::Poll(_))));
+            socket.send_to(&ArtCommand::PollReply(Box::new(reply)).write_to_buffer().unwrap(), poller).unwrap();
+        });
+
+        (addr, handle)
+    }
+
+    fn name<const N: usize>(name: &str) -> [u8; N] {
+        let mut bytes = [0u8; N];
+        bytes[..name.len()].copy_from_slice(name.as_bytes());
+        bytes
+    }
+
+    fn poll_reply(net: u8, subnet: u8, num_ports: u8, swout: [u8; 4]) -> PollReply {
+        PollReply {
+            address: Ipv4Addr::LOCALHOST,
+            port: ARTNET_PORT,
+            version: [0, 1],
+            port_address: [net, subnet],
+            oem: [0, 0],
+            ubea_version: 0,
+            status_1: 0,
+            esta_code: 0,
+            short_name: name("WLED"),
+            long_name: name("WLED Matrix"),
+            node_report: [0; 64],
+            num_ports: [0, num_ports],
+            port_types: [0x80; 4],
+            good_input: [0; 4],
+            good_output: [0; 4],
+            swin: [0; 4],
+            swout,
+            sw_video: 0,
+            sw_macro: 0,
+            sw_remote: 0,
+            spare: [0; 3],
+            style: 0,
+            mac: [0; 6],
+            bind_ip: [127, 0, 0, 1],
+            bind_index: 1,
+            status_2: 0,
+            filler: [0; 26],
+        }
+    }
+
+    #[test]
+    fn discovers_fake_node_on_loopback() {
+        let (addr, node) = spawn_fake_node(poll_reply(1, 2, 2, [3, 4, 0, 0]));
+
+        let nodes = discover(addr, Duration::from_millis(500)).unwrap();
+        node.join().unwrap();
+
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(nodes[0].ip, IpAddr::V4(Ipv4Addr::LOCALHOST));
+        assert_eq!(nodes[0].short_name, "WLED");
+        assert_eq!(nodes[0].long_name, "WLED Matrix");
+        // net 1, subnet 2, only the first 2 of the 4 ports
+        assert_eq!(nodes[0].universes, vec![0x123, 0x124]);
+    }
+
+    #[test]
+    fn parses_broadcast_addr() {
+        assert_eq!(get_broadcast_addr(None), Some(SocketAddr::new(IpAddr::V4(Ipv4Addr::BROADCAST), ARTNET_PORT)));
+        assert_eq!(get_broadcast_addr(Some("192.168.1.255")).map(|addr| addr.ip().to_string()), Some("192.168.1.255".to_string()));
+        assert_eq!(get_broadcast_addr(Some("not an ip")), None);
+    }
+}
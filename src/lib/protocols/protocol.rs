@@ -0,0 +1,40 @@
+use std::net::{SocketAddr, ToSocketAddrs};
+use crate::lib::models::frame::AnimationFrame;
+use crate::settings::{Protocol, Target};
+use super::artnet::ArtNetProtocol;
+use super::ddp::DdpProtocol;
+use super::sacn::SacnProtocol;
+use super::wled::WledProtocol;
+
+/// A single datagram, ready to be sent to a target device
+pub struct Packet {
+    pub addr: SocketAddr,
+    pub data: Vec<u8>,
+}
+
+/// Output protocol used to encode frames for a target device.
+///
+/// Implementations keep per-target state (e.g. sequence numbers),
+/// so a new instance should be created for each target.
+pub trait OutputProtocol: Send {
+    /// Encodes a single frame into the packets that make up the frame on the wire
+    fn encode_frame(&mut self, frame: &AnimationFrame) -> Vec<Packet>;
+}
+
+///
+/// Creates the output protocol configured for the given target.
+///
+/// `target` - resolved IP address of the target device, without port
+/// `device` - the target settings
+pub fn get_protocol(target: &str, device: &Target) -> Box<dyn OutputProtocol> {
+    match device.protocol {
+        Protocol::ArtNet => Box::new(ArtNetProtocol::new(target, device)),
+        Protocol::Sacn => Box::new(SacnProtocol::new(target, device)),
+        Protocol::Ddp => Box::new(DdpProtocol::new(target, device)),
+        Protocol::Wled => Box::new(WledProtocol::new(target, device)),
+    }
+}
+
+pub fn get_socket_addr(target: &str, port: u16) -> SocketAddr {
+    format!("{}:{}", target, port).to_socket_addrs().unwrap().next().unwrap()
+}
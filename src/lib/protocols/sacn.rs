@@ -0,0 +1,176 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use crate::lib::models::frame::AnimationFrame;
+use crate::settings::Target;
+use super::protocol::{get_socket_addr, OutputProtocol, Packet};
+use super::universe::UniverseMapping;
+
+static SACN_PORT: u16 = 5568;
+static DEFAULT_PRIORITY: u8 = 100;
+static DEFAULT_SOURCE_NAME: &str = "rustify-wled";
+
+// E1.31 packet constants
+static ACN_PACKET_IDENTIFIER: &[u8; 12] = b"ASC-E1.17\0\0\0";
+static PREAMBLE_SIZE: u16 = 0x0010;
+static POSTAMBLE_SIZE: u16 = 0x0000;
+static VECTOR_ROOT_E131_DATA: u32 = 0x0000_0004;
+static VECTOR_ROOT_E131_EXTENDED: u32 = 0x0000_0008;
+static VECTOR_E131_DATA_PACKET: u32 = 0x0000_0002;
+static VECTOR_E131_EXTENDED_SYNCHRONIZATION: u32 = 0x0000_0001;
+static VECTOR_DMP_SET_PROPERTY: u8 = 0x02;
+static DMP_ADDRESS_AND_DATA_TYPE: u8 = 0xa1;
+static DMX_START_CODE: u8 = 0x00;
+
+// offsets of each layer's flags & length field, lengths are counted from these
+static ROOT_LAYER_OFFSET: usize = 16;
+static FRAMING_LAYER_OFFSET: usize = 38;
+static DMP_LAYER_OFFSET: usize = 115;
+static SYNC_PACKET_LENGTH: usize = 49;
+
+/// E1.31 (sACN) output, sending each shard of a frame as an E1.31 data packet
+///
+/// `target` - address of the target device, used when not in multicast mode
+/// `mapping` - mapping of the frame data onto the target's universes
+/// `sync_universe` - universe to send sync packets on after each frame, if enabled
+/// `multicast` - whether to send to the universe's multicast group instead of the target
+/// `priority` - priority of this source, 0 - 200
+/// `source_name` - null-padded, user-assigned name of this source
+/// `cid` - component identifier of this source, derived from the source name
+/// `sequences` - sequence number of each universe
+/// `sync_sequence` - sequence number of sync packets, separate from data packets
+pub struct SacnProtocol {
+    target: SocketAddr,
+    mapping: UniverseMapping,
+    sync_universe: Option<u16>,
+    multicast: bool,
+    priority: u8,
+    source_name: [u8; 64],
+    cid: [u8; 16],
+    sequences: HashMap<u16, u8>,
+    sync_sequence: u8,
+}
+
+impl SacnProtocol {
+    pub fn new(target: &str, device: &Target) -> Self {
+        let name = device.source_name.clone().unwrap_or(DEFAULT_SOURCE_NAME.to_string());
+
+        let mapping = UniverseMapping::from_target(device);
+        let sync_universe = match device.sync.unwrap_or(false) {
+            true => Some(device.sync_universe.unwrap_or(mapping.start_universe as u16)),
+            false => None,
+        };
+
+        let mut source_name = [0u8; 64];
+        let name_length = name.len().min(source_name.len() - 1);
+        source_name[..name_length].copy_from_slice(&name.as_bytes()[..name_length]);
+
+        Self {
+            target: get_socket_addr(target, SACN_PORT),
+            mapping,
+            sync_universe,
+            multicast: device.multicast.unwrap_or(false),
+            priority: device.priority.unwrap_or(DEFAULT_PRIORITY),
+            source_name,
+            cid: md5::compute(name.as_bytes()).0,
+            sequences: HashMap::new(),
+            sync_sequence: 0,
+        }
+    }
+
+    /// Multicast address of a universe: 239.255.{universe high byte}.{universe low byte}
+    fn multicast_addr(universe: u16) -> SocketAddr {
+        let [high, low] = universe.to_be_bytes();
+        SocketAddr::new(IpAddr::V4(Ipv4Addr::new(239, 255, high, low)), SACN_PORT)
+    }
+
+    fn next_sequence(&mut self, universe: u16) -> u8 {
+        let sequence = self.sequences.entry(universe).or_insert(0);
+        *sequence = sequence.wrapping_add(1);
+        *sequence
+    }
+
+    /// Root layer, shared by data and sync packets
+    fn root_layer(&self, packet: &mut Vec<u8>, vector: u32, length: usize) {
+        packet.extend_from_slice(&PREAMBLE_SIZE.to_be_bytes());
+        packet.extend_from_slice(&POSTAMBLE_SIZE.to_be_bytes());
+        packet.extend_from_slice(ACN_PACKET_IDENTIFIER);
+        packet.extend_from_slice(&flags_and_length(length - ROOT_LAYER_OFFSET));
+        packet.extend_from_slice(&vector.to_be_bytes());
+        packet.extend_from_slice(&self.cid);
+    }
+
+    /// Builds a complete E1.31 data packet (root, framing and DMP layers) for a single universe
+    fn data_packet(&self, universe: u16, sequence: u8, data: &[u8]) -> Vec<u8> {
+        // header is 126 bytes, including the DMX start code
+        let length = DMP_LAYER_OFFSET + 11 + data.len();
+        let mut packet = Vec::with_capacity(length);
+
+        self.root_layer(&mut packet, VECTOR_ROOT_E131_DATA, length);
+
+        // framing layer
+        packet.extend_from_slice(&flags_and_length(length - FRAMING_LAYER_OFFSET));
+        packet.extend_from_slice(&VECTOR_E131_DATA_PACKET.to_be_bytes());
+        packet.extend_from_slice(&self.source_name);
+        packet.push(self.priority);
+        packet.extend_from_slice(&self.sync_universe.unwrap_or(0).to_be_bytes()); // synchronization address, 0 = unsynchronized
+        packet.push(sequence);
+        packet.push(0); // options
+        packet.extend_from_slice(&universe.to_be_bytes());
+
+        // DMP layer
+        packet.extend_from_slice(&flags_and_length(length - DMP_LAYER_OFFSET));
+        packet.push(VECTOR_DMP_SET_PROPERTY);
+        packet.push(DMP_ADDRESS_AND_DATA_TYPE);
+        packet.extend_from_slice(&0u16.to_be_bytes()); // first property address
+        packet.extend_from_slice(&1u16.to_be_bytes()); // address increment
+        packet.extend_from_slice(&((data.len() + 1) as u16).to_be_bytes()); // property value count, including start code
+        packet.push(DMX_START_CODE);
+        packet.extend_from_slice(data);
+
+        packet
+    }
+
+    /// Builds an E1.31 synchronization packet, telling receivers to output the data received for `sync_universe`
+    fn sync_packet(&self, sync_universe: u16, sequence: u8) -> Vec<u8> {
+        let mut packet = Vec::with_capacity(SYNC_PACKET_LENGTH);
+
+        self.root_layer(&mut packet, VECTOR_ROOT_E131_EXTENDED, SYNC_PACKET_LENGTH);
+
+        // synchronization framing layer
+        packet.extend_from_slice(&flags_and_length(SYNC_PACKET_LENGTH - FRAMING_LAYER_OFFSET));
+        packet.extend_from_slice(&VECTOR_E131_EXTENDED_SYNCHRONIZATION.to_be_bytes());
+        packet.push(sequence);
+        packet.extend_from_slice(&sync_universe.to_be_bytes());
+        packet.extend_from_slice(&[0, 0]); // reserved
+
+        packet
+    }
+
+    fn addr(&self, universe: u16) -> SocketAddr {
+        match self.multicast {
+            true => Self::multicast_addr(universe),
+            false => self.target,
+        }
+    }
+}
+
+impl OutputProtocol for SacnProtocol {
+    fn encode_frame(&mut self, frame: &AnimationFrame) -> Vec<Packet> {
+        let mut packets: Vec<Packet> = self.mapping.shard(&frame.data).into_iter().map(|(universe, frame_slice)| {
+            let sequence = self.next_sequence(universe);
+            Packet { addr: self.addr(universe), data: self.data_packet(universe, sequence, &frame_slice) }
+        }).collect();
+
+        if let Some(sync_universe) = self.sync_universe {
+            self.sync_sequence = self.sync_sequence.wrapping_add(1);
+            packets.push(Packet { addr: self.addr(sync_universe), data: self.sync_packet(sync_universe, self.sync_sequence) });
+        }
+
+        packets
+    }
+}
+
+/// PDU flags (0x7) in the high 4 bits, PDU length in the low 12 bits
+fn flags_and_length(length: usize) -> [u8; 2] {
+    (0x7000 | (length as u16 & 0x0fff)).to_be_bytes()
+}
@@ -0,0 +1,100 @@
+use std::ops::Range;
+use crate::settings::{Protocol, Target};
+
+pub static MAX_CHANNELS_PER_UNIVERSE: u16 = 512;
+pub static ARTNET_MAX_PORT_ADDRESS: u32 = 32767;
+pub static SACN_MAX_UNIVERSE: u32 = 63999;
+
+/// Mapping of frame data onto the DMX universes of a target
+///
+/// `start_universe` - first universe of the target (for ArtNet, the 15-bit port address including net and subnet)
+/// `channels_per_universe` - number of channels used in each universe
+/// `channel_offset` - number of channels skipped in the first universe (WLED's "DMX start address" - 1)
+/// `channels_per_pixel` - number of channels of each LED, see `ChannelLayout`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UniverseMapping {
+    pub start_universe: u32,
+    pub channels_per_universe: usize,
+    pub channel_offset: usize,
+    pub channels_per_pixel: usize,
+}
+
+impl UniverseMapping {
+    pub fn from_target(device: &Target) -> Self {
+        Self {
+            start_universe: Self::start_universe(device),
+            channels_per_universe: device.channels_per_universe.unwrap_or(Self::default_channels_per_universe(device)) as usize,
+            channel_offset: device.start_channel.unwrap_or(1).saturating_sub(1) as usize,
+            channels_per_pixel: device.channels.channels_per_pixel(),
+        }
+    }
+
+    ///
+    /// First universe of the target, for ArtNet the port address built from net, subnet and universe.
+    ///
+    /// Computed in u32, so out of range settings can't overflow before they are validated.
+    pub fn start_universe(device: &Target) -> u32 {
+        match device.protocol {
+            // universe 0 is reserved in E1.31
+            Protocol::Sacn => device.start_universe.unwrap_or(1) as u32,
+            _ => {
+                ((device.net.unwrap_or(0) as u32) << 8)
+                    + ((device.subnet.unwrap_or(0) as u32) << 4)
+                    + device.start_universe.unwrap_or(0) as u32
+            },
+        }
+    }
+
+    /// As many whole pixels as fit in a universe, like WLED (e.g. 170 RGB pixels/510 channels, even though the max is 512)
+    pub fn default_channels_per_universe(device: &Target) -> u16 {
+        let channels_per_pixel = device.channels.channels_per_pixel() as u16;
+        MAX_CHANNELS_PER_UNIVERSE - MAX_CHANNELS_PER_UNIVERSE % channels_per_pixel
+    }
+
+    ///
+    /// Splits the frame data into (universe, DMX data) pairs, in universe order.
+    ///
+    /// The DMX data of the first universe is padded with `channel_offset` empty channels.
+    ///
+    /// NOTE: the universes must have been validated to fit the protocol (see `Settings::validate_universes`)
+    pub fn shard(&self, data: &[u8]) -> Vec<(u16, Vec<u8>)> {
+        let universe = |i: usize| u16::try_from(self.start_universe + i as u32).expect("Universes are validated to fit in 16 bits");
+
+        let (first, rest) = data.split_at(self.first_universe_channels().min(data.len()));
+
+        let mut first_shard = vec![0; self.channel_offset];
+        first_shard.extend_from_slice(first);
+
+        let mut shards = vec![(universe(0), first_shard)];
+        shards.extend(rest.chunks(self.channels_per_universe).enumerate().map(|(i, shard)| {
+            (universe(i + 1), shard.to_vec())
+        }));
+
+        shards
+    }
+
+    /// Universes needed to send `num_channels` channels of frame data
+    pub fn universes(&self, num_channels: usize) -> Range<u32> {
+        let remaining = num_channels.saturating_sub(self.first_universe_channels());
+        let count = 1 + remaining.div_ceil(self.channels_per_universe) as u32;
+
+        self.start_universe..self.start_universe + count
+    }
+
+    /// Universes used by the given target
+    pub fn universes_for_target(device: &Target) -> Range<u32> {
+        Self::from_target(device).universes(device.num_sent_pixels() * device.channels.channels_per_pixel())
+    }
+
+    /// Number of channels of frame data that fit in the first universe.
+    ///
+    /// When the universe size is a multiple of the pixel size, pixels are never split across universes.
+    fn first_universe_channels(&self) -> usize {
+        let available = self.channels_per_universe.saturating_sub(self.channel_offset);
+
+        match self.channels_per_universe % self.channels_per_pixel {
+            0 => available - available % self.channels_per_pixel,
+            _ => available,
+        }
+    }
+}
@@ -0,0 +1,110 @@
+use std::net::SocketAddr;
+use crate::lib::models::frame::AnimationFrame;
+use crate::settings::{RealtimeMode, Target};
+use super::protocol::{get_socket_addr, OutputProtocol, Packet};
+
+static WLED_REALTIME_PORT: u16 = 21324;
+// seconds until WLED returns to its normal effect after the last packet, 255 disables the timeout
+static DEFAULT_REALTIME_TIMEOUT: u8 = 2;
+
+// maximum number of LEDs in a single packet for each mode
+pub static WARLS_MAX_PIXELS: usize = 255;
+pub static DRGB_MAX_PIXELS: usize = 490;
+pub static DRGBW_MAX_PIXELS: usize = 367;
+pub static DNRGB_MAX_PIXELS: usize = 489;
+
+/// WLED's native realtime UDP output (WARLS, DRGB, DRGBW, DNRGB)
+///
+/// If no mode is configured, DRGB is used when the frame fits in a single packet, DNRGB otherwise.
+///
+/// `addr` - address of the target device
+/// `mode` - the realtime protocol to use, auto-selected if None
+/// `timeout` - realtime timeout sent with every packet
+/// `channels_per_pixel` - 4 for RGBW targets (DRGBW only), 3 otherwise
+pub struct WledProtocol {
+    addr: SocketAddr,
+    mode: Option<RealtimeMode>,
+    timeout: u8,
+    channels_per_pixel: usize,
+}
+
+impl WledProtocol {
+    pub fn new(target: &str, device: &Target) -> Self {
+        Self {
+            addr: get_socket_addr(target, WLED_REALTIME_PORT),
+            mode: device.realtime_mode,
+            timeout: device.realtime_timeout.unwrap_or(DEFAULT_REALTIME_TIMEOUT),
+            channels_per_pixel: device.channels.channels_per_pixel(),
+        }
+    }
+
+    fn header(&self, mode: RealtimeMode) -> Vec<u8> {
+        vec![mode as u8, self.timeout]
+    }
+
+    /// WARLS: [index, r, g, b] for each LED, only the first 255 LEDs are addressable
+    fn warls_packets(&self, pixels: Vec<&[u8]>) -> Vec<Vec<u8>> {
+        let mut packet = self.header(RealtimeMode::Warls);
+
+        for (i, pixel) in pixels.into_iter().take(WARLS_MAX_PIXELS).enumerate() {
+            packet.push(i as u8);
+            packet.extend_from_slice(pixel);
+        }
+
+        vec![packet]
+    }
+
+    /// DRGB: [r, g, b] for each LED, starting from the first LED
+    fn drgb_packets(&self, pixels: Vec<&[u8]>) -> Vec<Vec<u8>> {
+        let mut packet = self.header(RealtimeMode::Drgb);
+        pixels.into_iter().take(DRGB_MAX_PIXELS).for_each(|pixel| packet.extend_from_slice(pixel));
+
+        vec![packet]
+    }
+
+    /// DRGBW: [r, g, b, w] for each LED, starting from the first LED
+    ///
+    /// NOTE: the white channel is left off, unless the target has `rgbw` channels
+    fn drgbw_packets(&self, pixels: Vec<&[u8]>) -> Vec<Vec<u8>> {
+        let mut packet = self.header(RealtimeMode::Drgbw);
+
+        for pixel in pixels.into_iter().take(DRGBW_MAX_PIXELS) {
+            packet.extend_from_slice(pixel);
+            if pixel.len() < 4 {
+                packet.push(0);
+            }
+        }
+
+        vec![packet]
+    }
+
+    /// DNRGB: [start index high, start index low], then [r, g, b] for each LED from the start index
+    fn dnrgb_packets(&self, pixels: Vec<&[u8]>) -> Vec<Vec<u8>> {
+        pixels.chunks(DNRGB_MAX_PIXELS).enumerate().map(|(i, chunk)| {
+            let mut packet = self.header(RealtimeMode::Dnrgb);
+            packet.extend_from_slice(&((i * DNRGB_MAX_PIXELS) as u16).to_be_bytes());
+            chunk.iter().for_each(|pixel| packet.extend_from_slice(pixel));
+            packet
+        }).collect()
+    }
+}
+
+impl OutputProtocol for WledProtocol {
+    fn encode_frame(&mut self, frame: &AnimationFrame) -> Vec<Packet> {
+        let pixels: Vec<&[u8]> = frame.data.chunks(self.channels_per_pixel).collect();
+
+        let mode = self.mode.unwrap_or(match pixels.len() {
+            n if n <= DRGB_MAX_PIXELS => RealtimeMode::Drgb,
+            _ => RealtimeMode::Dnrgb,
+        });
+
+        let packets = match mode {
+            RealtimeMode::Warls => self.warls_packets(pixels),
+            RealtimeMode::Drgb => self.drgb_packets(pixels),
+            RealtimeMode::Drgbw => self.drgbw_packets(pixels),
+            RealtimeMode::Dnrgb => self.dnrgb_packets(pixels),
+        };
+
+        packets.into_iter().map(|data| Packet { addr: self.addr, data }).collect()
+    }
+}
@@ -1,15 +1,18 @@
 #[macro_use] extern crate rocket;
 
-use std::sync::mpsc;
+use std::env;
 
+use rocket::{Build, Rocket};
 use rocket::response::Redirect;
 use rocket::futures::future::Either;
+use rocket::http::Status;
+use rocket::serde::json::Json;
 use rocket::State;
-use rustify_wled_lib::lib::controllers::animation::{AnimationController, AnimationControllerConfig};
+use rustify_wled_lib::lib::controllers::animation::AnimationController;
 use rustify_wled_lib::lib::controllers::app::ApplicationController;
 use rustify_wled_lib::lib::controllers::spotify::SpotifyController;
 use rustify_wled_lib::lib::models::app_channels::AppChannels;
-use rustify_wled_lib::utils::network::resolve_ip;
+use rustify_wled_lib::lib::protocols::discovery::{discover, get_broadcast_addr, DiscoveredNode, DISCOVERY_TIMEOUT};
 
 ///
 /// Responses for starting the application,
@@ -20,12 +23,6 @@ pub enum StartResponses {
     String(String),
 }
 
-
-#[get("/")]
-fn index() -> &'static str {
-    "Hello, world!"
-}
-
 #[get("/start")]
 fn anim_start(controller: &State<ApplicationController>) -> StartResponses {
     match controller.start().unwrap() {
@@ -48,10 +45,60 @@ fn callback(controller: &State<ApplicationController>, code: String) -> StartRes
     }
 }
 
-#[launch]
-fn rocket() -> _ {
+///
+/// Discovers ArtNet nodes on the network with ArtPoll
+///
+/// Discovery blocks until the ArtPoll timeout, so it runs on a blocking thread instead of an async worker.
+///
+/// `broadcast` - IP to send the ArtPoll to, defaults to 255.255.255.255
+#[get("/discover?<broadcast>")]
+async fn artnet_discover(broadcast: Option<&str>) -> Result<Json<Vec<DiscoveredNode>>, Status> {
+    let broadcast_addr = get_broadcast_addr(broadcast).ok_or(Status::BadRequest)?;
+
+    match rocket::tokio::task::spawn_blocking(move || discover(broadcast_addr, DISCOVERY_TIMEOUT)).await {
+        Ok(Ok(nodes)) => Ok(Json(nodes)),
+        _ => Err(Status::InternalServerError),
+    }
+}
+
+///
+/// CLI subcommand, prints a `[[targets]]` block for each discovered ArtNet node
+///
+/// Usage: `cargo run -- discover [broadcast ip]`
+fn discover_command(broadcast: Option<&str>) {
+    let broadcast_addr = get_broadcast_addr(broadcast).expect("Invalid broadcast IP");
+
+    println!("Discovering ArtNet nodes via {}...", broadcast_addr);
+    let nodes = discover(broadcast_addr, DISCOVERY_TIMEOUT).expect("Failed to discover ArtNet nodes");
+
+    if nodes.is_empty() {
+        println!("No ArtNet nodes found");
+    }
+
+    for node in nodes {
+        println!("\n{}", node.to_target_config());
+    }
+}
+
+fn main() {
     env_logger::init();
 
+    let args: Vec<String> = env::args().collect();
+
+    match args.get(1).map(|arg| arg.as_str()) {
+        Some("discover") => discover_command(args.get(2).map(|arg| arg.as_str())),
+        _ => {
+            // restore the targets once the server has shut down (e.g. Ctrl-C)
+            if let Ok(rocket) = rocket::execute(rocket().launch()) {
+                if let Some(app_controller) = rocket.state::<ApplicationController>() {
+                    app_controller.shutdown();
+                }
+            }
+        },
+    }
+}
+
+fn rocket() -> Rocket<Build> {
     let channels: AppChannels = AppChannels::setup();
 
     let animation_controller: AnimationController = AnimationController::new(channels.anim_msg_rx);
@@ -69,5 +116,6 @@ fn rocket() -> _ {
     .mount("/", routes![anim_start])
     .mount("/", routes![anim_stop])
     .mount("/", routes![callback])
+    .mount("/", routes![artnet_discover])
     .manage(app_controller)
 }
\ No newline at end of file
@@ -1,11 +1,34 @@
 use std::sync::RwLock;
+use std::thread;
 use once_cell::sync::Lazy;
 use config::{Config, ConfigError, File};
-use log::warn;
+use log::{info, warn};
 use serde_derive::Deserialize;
-use crate::utils::network::resolve_ip;
+use crate::lib::artnet::anim::effects::base::font::GLYPH_HEIGHT;
+use crate::lib::protocols::universe::{UniverseMapping, ARTNET_MAX_PORT_ADDRESS, MAX_CHANNELS_PER_UNIVERSE, SACN_MAX_UNIVERSE};
+use crate::lib::protocols::wled::{DRGBW_MAX_PIXELS, DRGB_MAX_PIXELS, WARLS_MAX_PIXELS};
+use crate::utils::network::{is_broadcast, resolve_ip};
+use crate::utils::wled::get_info;
 
 static SPOTIFY_POLLING_SECONDS_WARNING: u64 = 1;
+static SACN_MAX_PRIORITY: u8 = 200;
+static SACN_MAX_SOURCE_NAME_LENGTH: usize = 63;
+static ARTNET_MAX_NET: u8 = 127;
+static ARTNET_MAX_SUBNET: u8 = 15;
+static ARTNET_MAX_UNIVERSE: u16 = 15;
+pub static DEFAULT_TRANSITION_DURATION: f64 = 1.0;
+pub static DEFAULT_TRACK_TEXT_SPEED: f64 = 20.0;
+pub static DEFAULT_PALETTE_SIZE: u8 = 4;
+pub static DEFAULT_CHASE_SPEED: f64 = 5.0;
+static MAX_PALETTE_SIZE: u8 = 16;
+pub static DEFAULT_WHITE_TEMPERATURE: u16 = 4500;
+pub static DEFAULT_WARM_WHITE_TEMPERATURE: u16 = 2700;
+pub static DEFAULT_COOL_WHITE_TEMPERATURE: u16 = 6500;
+static MIN_WHITE_TEMPERATURE: u16 = 1000;
+static MAX_WHITE_TEMPERATURE: u16 = 40000;
+static MIN_GAMMA: f64 = 0.1;
+static MAX_GAMMA: f64 = 5.0;
+pub static LUT_SIZE: usize = 256;
 
 
 #[derive(Debug, Deserialize)]
@@ -22,11 +45,407 @@ pub struct Spotify {
     pub(crate) precache_albums: Option<u8>,
 }
 
+/// Output protocol used to send frames to a target
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Protocol {
+    #[default]
+    ArtNet,
+    Sacn,
+    Ddp,
+    Wled,
+}
+
+impl Protocol {
+    /// Whether the protocol carries DMX universes, in which case WLED's DMX mode
+    /// (e.g. `Single RGB` for 1-D targets) determines how the data is displayed
+    pub fn is_dmx(&self) -> bool {
+        matches!(self, Protocol::ArtNet | Protocol::Sacn)
+    }
+}
+
+/// What to do with a target when the animation stops, times out, or the application shuts down
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum RestoreMode {
+    // restore the state of the device from before the animation started
+    #[default]
+    State,
+    // apply the preset in `restore_preset`
+    Preset,
+    // power off the device
+    Off,
+    // leave the device as is, it will return to its normal effect after WLED's realtime timeout
+    None,
+}
+
+/// WLED realtime UDP protocol, the discriminant is the protocol byte of the packet
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum RealtimeMode {
+    Warls = 1,
+    Drgb = 2,
+    Drgbw = 3,
+    Dnrgb = 4,
+}
+
+/// Channels of each LED of a target, in the order they are sent
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ChannelLayout {
+    #[default]
+    Rgb,
+    Grb,
+    Bgr,
+    // red, green, blue and white
+    Rgbw,
+    // red, green, blue, warm white and cool white
+    Rgbww,
+}
+
+impl ChannelLayout {
+    pub fn channels_per_pixel(&self) -> usize {
+        match self {
+            ChannelLayout::Rgb | ChannelLayout::Grb | ChannelLayout::Bgr => 3,
+            ChannelLayout::Rgbw => 4,
+            ChannelLayout::Rgbww => 5,
+        }
+    }
+
+    /// Whether the layout has white channel(s), extracted from the RGB frames at output time
+    pub fn has_white(&self) -> bool {
+        matches!(self, ChannelLayout::Rgbw | ChannelLayout::Rgbww)
+    }
+}
+
+/// How the white channel(s) of RGBW/RGBWW targets are extracted from RGB, see `ChannelEncoder`
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum WhiteMode {
+    // the white shared by all color channels (their minimum) is moved to the white channel(s)
+    #[default]
+    MinSubtract,
+    // the part of the color matching the color temperature of the white LED(s) is moved to the white channel(s)
+    ColorTemperature,
+}
+
+/// White channel extraction of RGBW/RGBWW targets
+///
+/// `mode` - how the white channel(s) are extracted
+/// `temperature` - color temperature of the white LEDs of RGBW targets in Kelvin, defaults to `DEFAULT_WHITE_TEMPERATURE`
+/// `warm_temperature`, `cool_temperature` - color temperatures of the white LEDs of RGBWW targets in Kelvin,
+///     default to `DEFAULT_WARM_WHITE_TEMPERATURE` and `DEFAULT_COOL_WHITE_TEMPERATURE`
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct White {
+    #[serde(default)]
+    pub(crate) mode: WhiteMode,
+    pub(crate) temperature: Option<u16>,
+    pub(crate) warm_temperature: Option<u16>,
+    pub(crate) cool_temperature: Option<u16>,
+}
+
+/// Color correction of a target, see `ColorCorrection`
+///
+/// `gamma` - exponent of the brightness curve, defaults to 1.0 (linear)
+/// `lut` - brightness curve as 256 output values, one per input value, instead of `gamma`
+/// `gains` - red, green and blue gains (0.0 - 1.0) for white balance, default to 1.0
+/// `temperature` - color temperature of white in Kelvin, e.g. lower for a warmer white, none by default
+/// `min_brightness` - lowest output value of a channel that is on, so dim colors don't turn off, defaults to 0
+/// `dither` - whether to dither over time, smoothing dim fades at the cost of slight flicker, defaults to false
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct Calibration {
+    pub(crate) gamma: Option<f64>,
+    pub(crate) lut: Option<Vec<u8>>,
+    pub(crate) gains: Option<[f64; 3]>,
+    pub(crate) temperature: Option<u16>,
+    pub(crate) min_brightness: Option<u8>,
+    pub(crate) dither: Option<bool>,
+}
+
+/// Transition played when the animation changes, e.g. on track change
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum TransitionKind {
+    // blend the outgoing animation into the incoming one
+    #[default]
+    Crossfade,
+    // fade the outgoing animation out to black, then fade the incoming one in
+    Fade,
+    // switch to the incoming animation immediately
+    Cut,
+    // 2-D only (1-D targets crossfade instead), see `SpatialTransitionEffects`
+    WipeHorizontal,
+    WipeVertical,
+    Slide,
+    Radial,
+    Dissolve,
+    Pixelate,
+}
+
+impl TransitionKind {
+    /// Whether the transition depends on the position of each pixel, and so needs a 2-D target
+    pub fn is_spatial(&self) -> bool {
+        !matches!(self, TransitionKind::Crossfade | TransitionKind::Fade | TransitionKind::Cut)
+    }
+}
+
+/// Color of the progress bar overlay
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ProgressBarColor {
+    // most dominant color of the cover
+    #[default]
+    Dominant,
+    // second most dominant color of the cover
+    Accent,
+    White,
+}
+
+/// Progress bar overlay on the bottom row(s) of a 2-D target
+///
+/// `enabled` - whether the bar is shown, defaults to true if the section is present
+/// `row` - offset of the bar from the bottom row, defaults to 0
+/// `thickness` - number of rows of the bar, defaults to 1
+/// `color` - color of the bar
+#[derive(Debug, Deserialize, Clone)]
+pub struct ProgressBar {
+    pub(crate) enabled: Option<bool>,
+    pub(crate) row: Option<u8>,
+    pub(crate) thickness: Option<u8>,
+    #[serde(default)]
+    pub(crate) color: ProgressBarColor,
+}
+
+/// "Artist – Title" scrolling across a 2-D target when the track changes
+///
+/// `enabled` - whether the text is shown, defaults to true if the section is present
+/// `speed` - scroll speed in pixels per second, defaults to `DEFAULT_TRACK_TEXT_SPEED`
+/// `repeat` - number of times the text scrolls across, defaults to 1
+#[derive(Debug, Deserialize, Clone)]
+pub struct TrackText {
+    pub(crate) enabled: Option<bool>,
+    pub(crate) speed: Option<f64>,
+    pub(crate) repeat: Option<u8>,
+}
+
+/// How the palette of the cover is laid out on a 1-D target
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum StripLayout {
+    // the most dominant color on the entire target
+    #[default]
+    Solid,
+    // blend from the most to the least dominant color along the target
+    Gradient,
+    // a block of equal length per color
+    Blocks,
+    // a looping gradient rotating along the target
+    Chase,
+}
+
+/// How the cover is fitted to a 2-D target with a different aspect ratio
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum FitMode {
+    // scale each dimension independently, distorting the cover
+    #[default]
+    Stretch,
+    // scale the entire cover to fit, filling the rest with the background color
+    Letterbox,
+    // scale the cover to fill the target, cropping the center
+    Crop,
+    // scale the cover to fill the target, cropping around its most salient region
+    SmartCrop,
+}
+
+/// Corner of a matrix (or panel) the first LED is wired at
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Corner {
+    #[default]
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+/// Whether consecutive LEDs of a matrix (or panel) run along its rows or its columns
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum WiringDirection {
+    #[default]
+    Horizontal,
+    Vertical,
+}
+
+/// Mirroring of the image on a matrix
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Mirror {
+    #[default]
+    None,
+    // left and right swapped
+    Horizontal,
+    // top and bottom swapped
+    Vertical,
+    Both,
+}
+
+/// Physical wiring of a 2-D target, see `PixelMap`
+///
+/// `start` - corner of the first LED of each panel
+/// `direction` - whether LEDs run along the rows or columns of each panel
+/// `serpentine` - whether every other row (or column) runs backwards, defaults to false
+/// `rotation` - clockwise rotation of the image in degrees (0, 90, 180 or 270), defaults to 0
+/// `mirror` - mirroring of the image, after rotation
+/// `tile_size` - width and height of each panel, defaults to the entire target (after rotation)
+/// `tile_order` - position of each panel in the chain, as the index of the panel in row-major order, defaults to row-major order
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct Mapping {
+    #[serde(default)]
+    pub(crate) start: Corner,
+    #[serde(default)]
+    pub(crate) direction: WiringDirection,
+    pub(crate) serpentine: Option<bool>,
+    pub(crate) rotation: Option<u16>,
+    #[serde(default)]
+    pub(crate) mirror: Mirror,
+    pub(crate) tile_size: Option<(u8, u8)>,
+    pub(crate) tile_order: Option<Vec<u16>>,
+}
+
+/// Logical canvas several targets are arranged on, so the cover is split across them (e.g. a video wall)
+///
+/// `size` - width and height of the canvas
+/// `fit` - how the cover is fitted to the canvas
+/// `background` - background color of the `letterbox` fit mode, defaults to black
+#[derive(Debug, Deserialize, Clone)]
+pub struct Canvas {
+    pub(crate) size: (u16, u16),
+    #[serde(default)]
+    pub(crate) fit: FitMode,
+    pub(crate) background: Option<[u8; 3]>,
+}
+
+/// Region of the canvas shown on a target
+///
+/// `offset` - position of the top left corner of the region on the canvas
+/// `size` - width and height of the region; 2-D targets default to their own size, and are scaled to it otherwise;
+///     1-D targets are laid out clockwise around the edge of the region, from its top left corner
+#[derive(Debug, Deserialize, Clone)]
+pub struct CanvasRegion {
+    pub(crate) offset: (u16, u16),
+    pub(crate) size: Option<(u16, u16)>,
+}
+
+/// Palette rendering of a 1-D target
+///
+/// `layout` - how the palette is laid out
+/// `palette_size` - number of colors taken from the cover, defaults to `DEFAULT_PALETTE_SIZE`
+/// `chase_speed` - speed of the `chase` layout in LEDs per second, defaults to `DEFAULT_CHASE_SPEED`
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct Strip {
+    #[serde(default)]
+    pub(crate) layout: StripLayout,
+    pub(crate) palette_size: Option<u8>,
+    pub(crate) chase_speed: Option<f64>,
+}
+
 #[derive(Debug, Deserialize, Clone)]
 #[allow(unused)]
 pub struct Target {
     pub(crate) host: String,
-    pub(crate) size: (u8, u8),
+    // detected from the device if not set, see `Settings::detect_sizes`
+    pub(crate) size: Option<(u8, u8)>,
+    #[serde(default)]
+    pub(crate) protocol: Protocol,
+    // DMX universe mapping (ArtNet and sACN), ignored by other protocols
+    pub(crate) start_universe: Option<u16>,
+    pub(crate) net: Option<u8>,
+    pub(crate) subnet: Option<u8>,
+    // ArtNet only: whether the host is a subnet broadcast address (e.g. x.x.x.255), 255.255.255.255 always is
+    pub(crate) broadcast: Option<bool>,
+    pub(crate) channels_per_universe: Option<u16>,
+    pub(crate) start_channel: Option<u16>,
+    // send a sync packet after each frame (ArtSync for ArtNet, universe sync for sACN)
+    pub(crate) sync: Option<bool>,
+    // sACN only, defaults to the first universe of the target
+    pub(crate) sync_universe: Option<u16>,
+    // sACN (E1.31) options, ignored by other protocols
+    pub(crate) priority: Option<u8>,
+    pub(crate) source_name: Option<String>,
+    pub(crate) multicast: Option<bool>,
+    #[serde(default)]
+    pub(crate) restore: RestoreMode,
+    pub(crate) restore_preset: Option<u8>,
+    // WLED realtime UDP options, ignored by other protocols
+    pub(crate) realtime_mode: Option<RealtimeMode>,
+    pub(crate) realtime_timeout: Option<u8>,
+    pub(crate) progress_bar: Option<ProgressBar>,
+    pub(crate) track_text: Option<TrackText>,
+    pub(crate) strip: Option<Strip>,
+    // how the cover is fitted to 2-D targets, and the background color of the `letterbox` mode (defaults to black)
+    #[serde(default)]
+    pub(crate) fit: FitMode,
+    pub(crate) background: Option<[u8; 3]>,
+    pub(crate) mapping: Option<Mapping>,
+    // region of the `[canvas]` shown on the target, instead of the entire cover
+    pub(crate) canvas: Option<CanvasRegion>,
+    // channels of each LED, and how white is extracted for RGBW/RGBWW
+    #[serde(default)]
+    pub(crate) channels: ChannelLayout,
+    pub(crate) white: Option<White>,
+    // color correction, applied before the channel layout
+    pub(crate) calibration: Option<Calibration>,
+}
+
+impl Target {
+    /// Size of the target, always set after the settings have been validated
+    pub fn size(&self) -> (u8, u8) {
+        self.size.expect("Target size is set during settings validation")
+    }
+
+    /// Number of LEDs of the target, dimension-agnostic
+    pub fn num_pixels(&self) -> usize {
+        self.size().0 as usize * (self.size().1 as usize).max(1)
+    }
+
+    /// Whether a single color is sent for the entire target, i.e. a 1-D DMX target not on the canvas with the `solid` strip layout
+    /// (for WLED's `Single RGB` DMX mode)
+    pub fn is_single_color(&self) -> bool {
+        let layout = self.strip.as_ref().map_or(StripLayout::Solid, |strip| strip.layout);
+        self.size().1 == 0 && self.canvas.is_none() && layout == StripLayout::Solid && self.protocol.is_dmx()
+    }
+
+    /// Number of pixels sent to the target: 1 if it shows a single color (see `is_single_color`), otherwise one per LED
+    pub fn num_sent_pixels(&self) -> usize {
+        match self.is_single_color() {
+            true => 1,
+            false => self.num_pixels(),
+        }
+    }
+
+    /// Progress bar settings of the target, if the progress bar is enabled
+    pub fn progress_bar(&self) -> Option<&ProgressBar> {
+        self.progress_bar.as_ref().filter(|progress_bar| progress_bar.enabled.unwrap_or(true))
+    }
+
+    ///
+    /// Region of the canvas shown on the target, if the target is on the canvas
+    ///
+    /// Returns:
+    ///     x, y, width and height of the region
+    pub fn canvas_region(&self) -> Option<(u16, u16, u16, u16)> {
+        self.canvas.as_ref().map(|region| {
+            let (width, height) = region.size.unwrap_or((self.size().0 as u16, self.size().1 as u16));
+            (region.offset.0, region.offset.1, width, height)
+        })
+    }
+
+    /// Track text settings of the target, if the track text is enabled
+    pub fn track_text(&self) -> Option<&TrackText> {
+        self.track_text.as_ref().filter(|track_text| track_text.enabled.unwrap_or(true))
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -34,6 +453,12 @@ pub struct Target {
 
 pub struct Animation {
     pub(crate) target_fps: u8,
+    #[serde(default)]
+    pub(crate) transition: TransitionKind,
+    // in seconds
+    pub(crate) transition_duration: Option<f64>,
+    // colors used when no vivid colors can be extracted from the cover, see `utils::palette`
+    pub(crate) fallback_palette: Option<Vec<[u8; 3]>>,
     #[serde(skip)]
     pub(crate) frame_interval: f64,
 }
@@ -45,6 +470,7 @@ pub struct Settings {
     pub(crate) spotify: Spotify,
     pub(crate) app: App,
     pub(crate) animation: Animation,
+    pub(crate) canvas: Option<Canvas>,
 }
 
 impl Settings {
@@ -62,7 +488,7 @@ impl Settings {
         Self::validate_settings(settings)
     }
 
-    fn validate_settings(settings: Settings) -> Result<(Settings), ConfigError> {
+    fn validate_settings(mut settings: Settings) -> Result<(Settings), ConfigError> {
         if settings.spotify.polling_seconds <= 0 {
             return Err(ConfigError::Message("Polling seconds must be greater than 0".to_string()));
         } else if settings.spotify.polling_seconds < SPOTIFY_POLLING_SECONDS_WARNING {
@@ -79,21 +505,463 @@ impl Settings {
             warn!("The ArtNet protocol does not exceed 40 FPS, you may be wasting processing power (current: {} FPS)", settings.animation.target_fps);
         }
 
+        if settings.animation.transition_duration.is_some_and(|duration| duration < 0.0 || duration.is_nan()) {
+            return Err(ConfigError::Message("Transition duration must not be negative".to_string()));
+        }
+
+        Self::detect_sizes(&mut settings.targets)?;
+
+        if settings.canvas.as_ref().is_some_and(|canvas| canvas.size.0 == 0 || canvas.size.1 == 0) {
+            return Err(ConfigError::Message("Canvas size must be at least 1 x 1".to_string()));
+        }
+
         for target in settings.targets.iter() {
-            if target.size.0 < 1 || target.size.1 < 0 {
-                return Err(ConfigError::Message(format!("Invalid target size {} x {} for {}", &target.size.0, &target.size.1, &target.host).to_string()));
-            } else if (target.size.0 as u16 * target.size.1 as u16) > 1500u16 {
-                warn!("Target size {} x {} for device {} exceeds the maximum number of LEDs WLED can drive: https://kno.wled.ge/interfaces/e1.31-dmx/, normal behavior is NOT GUARANTEED", &target.size.0, &target.size.1, &target.host);
+            let size = target.size();
+            if size.0 < 1 || size.1 < 0 {
+                return Err(ConfigError::Message(format!("Invalid target size {} x {} for {}", &size.0, &size.1, &target.host).to_string()));
+            } else if (size.0 as u16 * size.1 as u16) > 1500u16 {
+                warn!("Target size {} x {} for device {} exceeds the maximum number of LEDs WLED can drive: https://kno.wled.ge/interfaces/e1.31-dmx/, normal behavior is NOT GUARANTEED", &size.0, &size.1, &target.host);
+            }
+
+            if target.protocol == Protocol::Sacn {
+                if target.priority.is_some_and(|priority| priority > SACN_MAX_PRIORITY) {
+                    return Err(ConfigError::Message(format!("sACN priority for {} must be between 0 and {}", &target.host, SACN_MAX_PRIORITY)));
+                }
+
+                if target.source_name.as_ref().is_some_and(|name| name.len() > SACN_MAX_SOURCE_NAME_LENGTH) {
+                    return Err(ConfigError::Message(format!("sACN source name for {} must not exceed {} bytes", &target.host, SACN_MAX_SOURCE_NAME_LENGTH)));
+                }
+            }
+
+            match (target.protocol, target.channels) {
+                (Protocol::Wled, ChannelLayout::Rgb) => {},
+                (Protocol::Wled, ChannelLayout::Rgbw) if target.realtime_mode == Some(RealtimeMode::Drgbw) => {},
+                (Protocol::Wled, _) => {
+                    return Err(ConfigError::Message(format!("Target {} uses WLED realtime UDP, which only supports `rgb` channels, or `rgbw` with realtime mode `drgbw` (WLED applies the color order of its own LEDs)", &target.host)));
+                },
+                (Protocol::Ddp, ChannelLayout::Rgbww) => {
+                    return Err(ConfigError::Message(format!("Target {} uses DDP, which doesn't support `rgbww` channels", &target.host)));
+                },
+                _ => {},
+            }
+
+            // WLED's `Single RGB` DMX mode has no white channel
+            if target.is_single_color() && target.channels.has_white() {
+                return Err(ConfigError::Message(format!("Target {} shows a single color over ArtNet/sACN, which only supports `rgb` channels (WLED's `Single RGB` DMX mode), use a strip layout other than `solid` for white channels", &target.host)));
+            }
+
+            if let Some(white) = &target.white {
+                let temperatures = [white.temperature, white.warm_temperature, white.cool_temperature];
+
+                if !target.channels.has_white() {
+                    warn!("White settings are only used for `rgbw` and `rgbww` channels, ignoring them for target {}", &target.host);
+                } else if temperatures.iter().flatten().any(|temperature| !(MIN_WHITE_TEMPERATURE..=MAX_WHITE_TEMPERATURE).contains(temperature)) {
+                    return Err(ConfigError::Message(format!("White color temperatures of {} must be between {} and {} K", &target.host, MIN_WHITE_TEMPERATURE, MAX_WHITE_TEMPERATURE)));
+                } else if white.warm_temperature.unwrap_or(DEFAULT_WARM_WHITE_TEMPERATURE) >= white.cool_temperature.unwrap_or(DEFAULT_COOL_WHITE_TEMPERATURE) {
+                    return Err(ConfigError::Message(format!("Warm white color temperature of {} must be lower than the cool one", &target.host)));
+                }
+            }
+
+            if let Some(calibration) = &target.calibration {
+                if calibration.gamma.is_some_and(|gamma| !(MIN_GAMMA..=MAX_GAMMA).contains(&gamma)) {
+                    return Err(ConfigError::Message(format!("Gamma of {} must be between {} and {}", &target.host, MIN_GAMMA, MAX_GAMMA)));
+                } else if calibration.lut.as_ref().is_some_and(|lut| lut.len() != LUT_SIZE) {
+                    return Err(ConfigError::Message(format!("Brightness LUT of {} must have exactly {} values", &target.host, LUT_SIZE)));
+                } else if calibration.gains.is_some_and(|gains| gains.iter().any(|gain| !(0.0..=1.0).contains(gain))) {
+                    return Err(ConfigError::Message(format!("Color gains of {} must be between 0.0 and 1.0", &target.host)));
+                } else if calibration.temperature.is_some_and(|temperature| !(MIN_WHITE_TEMPERATURE..=MAX_WHITE_TEMPERATURE).contains(&temperature)) {
+                    return Err(ConfigError::Message(format!("Color temperature of {} must be between {} and {} K", &target.host, MIN_WHITE_TEMPERATURE, MAX_WHITE_TEMPERATURE)));
+                } else if calibration.lut.is_some() && calibration.gamma.is_some() {
+                    warn!("Target {} has both a brightness LUT and a gamma, ignoring the gamma", &target.host);
+                }
+            }
+
+            if target.protocol == Protocol::Wled {
+                let max_pixels = match target.realtime_mode {
+                    Some(RealtimeMode::Warls) => WARLS_MAX_PIXELS,
+                    Some(RealtimeMode::Drgb) => DRGB_MAX_PIXELS,
+                    Some(RealtimeMode::Drgbw) => DRGBW_MAX_PIXELS,
+                    Some(RealtimeMode::Dnrgb) | None => usize::MAX,
+                };
+
+                if target.num_pixels() > max_pixels {
+                    return Err(ConfigError::Message(format!("Target {} has {} LEDs, but realtime mode {:?} supports at most {}, use `dnrgb` instead", &target.host, target.num_pixels(), target.realtime_mode.unwrap(), max_pixels)));
+                }
+            }
+
+            if let Some(progress_bar) = target.progress_bar() {
+                let rows = progress_bar.row.unwrap_or(0) as u16 + progress_bar.thickness.unwrap_or(1) as u16;
+
+                if size.1 == 0 {
+                    warn!("Progress bar is only supported for 2-D targets, ignoring it for target {}", &target.host);
+                } else if progress_bar.thickness == Some(0) || rows > size.1 as u16 {
+                    return Err(ConfigError::Message(format!("Progress bar of {} must have a thickness of at least 1 and fit within its {} rows", &target.host, &size.1)));
+                }
+            }
+
+            if let Some(track_text) = target.track_text() {
+                if (size.1 as usize) < GLYPH_HEIGHT {
+                    warn!("Track text needs a 2-D target at least {} rows high, ignoring it for target {}", GLYPH_HEIGHT, &target.host);
+                } else if track_text.speed.is_some_and(|speed| speed <= 0.0 || speed.is_nan()) {
+                    return Err(ConfigError::Message(format!("Track text speed of {} must be greater than 0", &target.host)));
+                }
+            }
+
+            if let Some(strip) = &target.strip {
+                if size.1 != 0 {
+                    warn!("Strip settings are only used for 1-D targets, ignoring them for target {}", &target.host);
+                } else if strip.palette_size.is_some_and(|palette_size| !(1..=MAX_PALETTE_SIZE).contains(&palette_size)) {
+                    return Err(ConfigError::Message(format!("Palette size of {} must be between 1 and {}", &target.host, MAX_PALETTE_SIZE)));
+                } else if strip.chase_speed.is_some_and(|speed| speed.is_nan()) {
+                    return Err(ConfigError::Message(format!("Chase speed of {} must be a number", &target.host)));
+                } else if strip.layout != StripLayout::Solid && target.protocol.is_dmx() {
+                    let mode = match target.channels.has_white() {
+                        true => "Multi RGBW",
+                        false => "Multi RGB",
+                    };
+                    info!("Target {} shows multiple colors, make sure its WLED DMX mode is set to `{}`", &target.host, mode);
+                }
+            }
+
+            if target.fit != FitMode::Stretch && size.1 == 0 {
+                warn!("Fit mode is only used for 2-D targets, ignoring it for target {}", &target.host);
+            }
+
+            match (target.canvas_region(), &settings.canvas) {
+                (Some(_), None) => {
+                    return Err(ConfigError::Message(format!("Target {} has a canvas region, but no `[canvas]` is defined", &target.host)));
+                },
+                (Some((x, y, width, height)), Some(canvas)) => {
+                    if width == 0 || height == 0 {
+                        return Err(ConfigError::Message(format!("Canvas region of {} must have a size of at least 1 x 1 (1-D targets must set it)", &target.host)));
+                    } else if x as u32 + width as u32 > canvas.size.0 as u32 || y as u32 + height as u32 > canvas.size.1 as u32 {
+                        return Err(ConfigError::Message(format!("Canvas region {} x {} at ({}, {}) of {} exceeds the canvas size {} x {}", &width, &height, &x, &y, &target.host, &canvas.size.0, &canvas.size.1)));
+                    }
+
+                    if target.fit != FitMode::Stretch {
+                        warn!("Target {} is on the canvas, its fit mode is ignored in favor of the canvas'", &target.host);
+                    }
+                    if size.1 == 0 && target.strip.is_some() {
+                        warn!("Target {} is on the canvas, its strip settings are ignored", &target.host);
+                    }
+                },
+                _ => {},
+            }
+
+            if let Some(mapping) = &target.mapping {
+                // the panels are laid out on the rotated image
+                let rotation = mapping.rotation.unwrap_or(0);
+                let (width, height) = match rotation {
+                    90 | 270 => (size.1, size.0),
+                    _ => (size.0, size.1),
+                };
+                let tile_size = mapping.tile_size.unwrap_or((width, height));
+
+                if size.1 == 0 {
+                    warn!("Pixel mapping is only used for 2-D targets, ignoring it for target {}", &target.host);
+                } else if ![0, 90, 180, 270].contains(&rotation) {
+                    return Err(ConfigError::Message(format!("Rotation of {} must be one of 0, 90, 180 or 270", &target.host)));
+                } else if tile_size.0 == 0 || tile_size.1 == 0 || width % tile_size.0 != 0 || height % tile_size.1 != 0 {
+                    return Err(ConfigError::Message(format!("Tile size {} x {} of {} must evenly divide its (rotated) size {} x {}", &tile_size.0, &tile_size.1, &target.host, &width, &height)));
+                } else if let Some(tile_order) = &mapping.tile_order {
+                    let num_tiles = (width / tile_size.0) as u16 * (height / tile_size.1) as u16;
+                    let mut sorted = tile_order.clone();
+                    sorted.sort();
+
+                    if !sorted.into_iter().eq(0..num_tiles) {
+                        return Err(ConfigError::Message(format!("Tile order of {} must contain each tile index from 0 to {} exactly once", &target.host, num_tiles - 1)));
+                    }
+                }
+            }
+
+            if target.restore == RestoreMode::Preset && target.restore_preset.is_none() {
+                return Err(ConfigError::Message(format!("Restore mode for {} is `preset`, but `restore_preset` is not set", &target.host)));
+            }
+
+            if target.protocol.is_dmx() {
+                Self::validate_universes(target)?;
+            } else if target.sync.unwrap_or(false) {
+                warn!("Sync is only supported for ArtNet and sACN, ignoring it for target {}", &target.host);
             }
 
             resolve_ip(&target.host).expect(format!("Target address {} is unreachable! Please ensure proper connection or remove the device.", &target.host).as_str());
         }
 
+        Self::validate_universe_overlap(&settings.targets)?;
+
         Ok(settings)
     }
+
+    ///
+    /// Queries the target's WLED JSON API to fill in its size if not set, or verify it otherwise.
+    ///
+    /// A size mismatch only logs a warning, as the configured size may intentionally differ (e.g. to use part of a matrix).
+    ///
+    /// Detects the size of all targets without a configured size, querying their devices in parallel,
+    /// so offline devices delay the startup by at most one request timeout.
+    fn detect_sizes(targets: &mut [Target]) -> Result<(), ConfigError> {
+        thread::scope(|scope| {
+            let handles: Vec<_> = targets.iter_mut()
+                .filter(|target| target.size.is_none())
+                .map(|target| scope.spawn(move || Self::detect_size(target)))
+                .collect();
+
+            handles.into_iter().try_for_each(|handle| handle.join().unwrap())
+        })
+    }
+
+    ///
+    /// Detects the size of the target from the WLED JSON API, if it isn't configured.
+    ///
+    /// Targets with a configured size are not queried, so they don't depend on the JSON API being reachable.
+    fn detect_size(target: &mut Target) -> Result<(), ConfigError> {
+        if target.size.is_some() {
+            return Ok(());
+        }
+
+        let info = match get_info(&target.host) {
+            Ok(info) => info,
+            Err(e) => {
+                return Err(ConfigError::Message(format!("Size of {} is not set, and could not be detected from the WLED JSON API: {}", &target.host, e)));
+            },
+        };
+
+        info!("Found WLED device \"{}\" (version {}) at {}", &info.name, &info.ver, &target.host);
+
+        if info.live {
+            warn!("WLED device {} is currently receiving realtime data from another source, which may override the animation", &target.host);
+        }
+
+        let detected = info.size();
+
+        match (u8::try_from(detected.0), u8::try_from(detected.1)) {
+            (Ok(width), Ok(height)) => {
+                info!("Detected size {} x {} for {}", width, height, &target.host);
+                target.size = Some((width, height));
+            },
+            _ => {
+                return Err(ConfigError::Message(format!("Detected size {} x {} of {} exceeds the maximum supported size of 255 x 255, please set the size manually", &detected.0, &detected.1, &target.host)));
+            },
+        }
+
+        Ok(())
+    }
+
+    fn validate_universes(target: &Target) -> Result<(), ConfigError> {
+        let channels_per_universe = target.channels_per_universe.unwrap_or(UniverseMapping::default_channels_per_universe(target));
+        let channels_per_pixel = target.channels.channels_per_pixel() as u16;
+        if !(channels_per_pixel..=MAX_CHANNELS_PER_UNIVERSE).contains(&channels_per_universe) {
+            return Err(ConfigError::Message(format!("Channels per universe for {} must be between {} and {}", &target.host, channels_per_pixel, MAX_CHANNELS_PER_UNIVERSE)));
+        }
+
+        // there must be room for at least one pixel after the start channel
+        let start_channel = target.start_channel.unwrap_or(1);
+        if start_channel < 1 || start_channel > channels_per_universe - channels_per_pixel + 1 {
+            return Err(ConfigError::Message(format!("Start channel for {} must be between 1 and {}", &target.host, channels_per_universe - channels_per_pixel + 1)));
+        }
+
+        let universes = UniverseMapping::universes_for_target(target);
+
+        match target.protocol {
+            Protocol::Sacn => {
+                if target.net.is_some() || target.subnet.is_some() || target.broadcast.is_some() {
+                    warn!("Net, subnet and broadcast are ArtNet-only settings, ignoring them for sACN target {}", &target.host);
+                }
+
+                if universes.start < 1 || universes.end - 1 > SACN_MAX_UNIVERSE {
+                    return Err(ConfigError::Message(format!("Universes {} - {} of {} are out of the sACN range 1 - {}", universes.start, universes.end - 1, &target.host, SACN_MAX_UNIVERSE)));
+                }
+
+                if target.sync_universe.is_some_and(|universe| universe < 1 || universe as u32 > SACN_MAX_UNIVERSE) {
+                    return Err(ConfigError::Message(format!("sACN sync universe of {} must be between 1 and {}", &target.host, SACN_MAX_UNIVERSE)));
+                }
+            },
+            _ => {
+                if target.net.is_some_and(|net| net > ARTNET_MAX_NET) {
+                    return Err(ConfigError::Message(format!("ArtNet net for {} must be between 0 and {}", &target.host, ARTNET_MAX_NET)));
+                }
+
+                if target.subnet.is_some_and(|subnet| subnet > ARTNET_MAX_SUBNET) {
+                    return Err(ConfigError::Message(format!("ArtNet subnet for {} must be between 0 and {}", &target.host, ARTNET_MAX_SUBNET)));
+                }
+
+                // with net/subnet, the universe is the low 4 bits of the port address, otherwise the entire port address
+                if (target.net.is_some() || target.subnet.is_some()) && target.start_universe.is_some_and(|universe| universe > ARTNET_MAX_UNIVERSE) {
+                    return Err(ConfigError::Message(format!("ArtNet start universe for {} must be between 0 and {} when net or subnet is set", &target.host, ARTNET_MAX_UNIVERSE)));
+                }
+
+                if universes.end - 1 > ARTNET_MAX_PORT_ADDRESS {
+                    return Err(ConfigError::Message(format!("Universes {} - {} of {} exceed the maximum ArtNet port address {}", universes.start, universes.end - 1, &target.host, ARTNET_MAX_PORT_ADDRESS)));
+                }
+            },
+        }
+
+        Ok(())
+    }
+
+    ///
+    /// Checks that no two DMX targets receiving the same packets use overlapping universes.
+    ///
+    /// Targets receive the same packets if they share the same host and protocol,
+    /// if they are both multicast sACN targets, or if either is an ArtNet target on a broadcast address
+    /// (which every ArtNet node on the network receives): 255.255.255.255, or a host set as `broadcast`.
+    fn validate_universe_overlap(targets: &[Target]) -> Result<(), ConfigError> {
+        // resolved once, as each target is compared with every other one
+        let dmx_targets: Vec<(&Target, Option<String>)> = targets.iter()
+            .filter(|target| target.protocol.is_dmx())
+            .map(|target| (target, resolve_ip(&target.host).ok()))
+            .collect();
+
+        let multicast = |target: &Target| target.protocol == Protocol::Sacn && target.multicast.unwrap_or(false);
+        let broadcast = |target: &Target, ip: &Option<String>| {
+            target.protocol == Protocol::ArtNet && (target.broadcast.unwrap_or(false) || ip.as_ref().is_some_and(|ip| is_broadcast(ip)))
+        };
+
+        for (i, (target, ip)) in dmx_targets.iter().enumerate() {
+            for (other, other_ip) in dmx_targets.iter().skip(i + 1) {
+                let same_host = target.host == other.host || (ip.is_some() && ip == other_ip);
+                let same_receiver = target.protocol == other.protocol
+                    && (same_host
+                        || (multicast(target) && multicast(other))
+                        || broadcast(target, ip) || broadcast(other, other_ip));
+
+                let universes = UniverseMapping::universes_for_target(target);
+                let other_universes = UniverseMapping::universes_for_target(other);
+
+                if same_receiver && universes.start < other_universes.end && other_universes.start < universes.end {
+                    return Err(ConfigError::Message(format!(
+                        "Universes {} - {} of {} overlap with universes {} - {} of {}",
+                        universes.start, universes.end - 1, &target.host,
+                        other_universes.start, other_universes.end - 1, &other.host,
+                    )));
+                }
+            }
+        }
+
+        Ok(())
+    }
 }
 
 pub static SETTINGS: Lazy<RwLock<Settings>> = Lazy::new(|| {
     let settings = Settings::new().expect("Failed to load settings");
     RwLock::new(settings)
-});
\ No newline at end of file
+});
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::net::TcpListener;
+    use config::FileFormat;
+    use crate::utils::wled::tests::{info_json, serve};
+    use super::*;
+
+    /// Target from its `[[targets]]` entry in the config, without validation
+    pub(crate) fn target_from_toml(toml: &str) -> Target {
+        Config::builder()
+            .add_source(File::from_str(toml, FileFormat::Toml))
+            .build().unwrap()
+            .try_deserialize().unwrap()
+    }
+
+    fn target(host: &str, size: Option<(u8, u8)>) -> Target {
+        let size = size.map_or(String::new(), |(width, height)| format!("size = [{}, {}]", width, height));
+        target_from_toml(&format!("host = \"{}\"\n{}", host, size))
+    }
+
+    fn strip(host: &str, start_universe: u16, layout: &str) -> Target {
+        target_from_toml(&format!("host = \"{}\"\nsize = [200, 0]\nstart_universe = {}\n[strip]\nlayout = \"{}\"", host, start_universe, layout))
+    }
+
+    #[test]
+    fn counts_universes_of_strips_by_layout() {
+        // a single color fits in one universe, 200 RGB LEDs need two (170 per universe)
+        assert_eq!(UniverseMapping::universes_for_target(&strip("127.0.0.1", 0, "solid")), 0..1);
+        assert_eq!(UniverseMapping::universes_for_target(&strip("127.0.0.1", 0, "gradient")), 0..2);
+
+        let canvas_strip = target_from_toml("host = \"127.0.0.1\"\nsize = [200, 0]\n[canvas]\noffset = [0, 0]\nsize = [16, 16]");
+        assert_eq!(UniverseMapping::universes_for_target(&canvas_strip), 0..2);
+    }
+
+    #[test]
+    fn validates_range_of_gradient_strips() {
+        assert!(Settings::validate_universes(&strip("127.0.0.1", ARTNET_MAX_PORT_ADDRESS as u16, "solid")).is_ok());
+        assert!(Settings::validate_universes(&strip("127.0.0.1", ARTNET_MAX_PORT_ADDRESS as u16, "gradient")).is_err());
+    }
+
+    #[test]
+    fn validates_overlap_of_gradient_strips() {
+        assert!(Settings::validate_universe_overlap(&[strip("127.0.0.1", 0, "solid"), strip("127.0.0.1", 1, "solid")]).is_ok());
+        assert!(Settings::validate_universe_overlap(&[strip("127.0.0.1", 0, "gradient"), strip("127.0.0.1", 1, "solid")]).is_err());
+        assert!(Settings::validate_universe_overlap(&[strip("127.0.0.1", 0, "gradient"), strip("127.0.0.1", 2, "gradient")]).is_ok());
+    }
+
+    #[test]
+    fn checks_overlap_with_broadcast_targets() {
+        let artnet = |host: &str, start_universe: u16, broadcast: bool| {
+            target_from_toml(&format!("host = \"{}\"\nsize = [16, 16]\nstart_universe = {}\nbroadcast = {}", host, start_universe, broadcast))
+        };
+
+        assert!(Settings::validate_universe_overlap(&[artnet("255.255.255.255", 0, false), artnet("127.0.0.1", 1, false)]).is_err());
+        // on a /23 or larger network, x.x.x.255 is an ordinary host
+        assert!(Settings::validate_universe_overlap(&[artnet("192.168.0.255", 0, false), artnet("127.0.0.1", 1, false)]).is_ok());
+        assert!(Settings::validate_universe_overlap(&[artnet("192.168.0.255", 0, true), artnet("127.0.0.1", 1, false)]).is_err());
+        assert!(Settings::validate_universe_overlap(&[artnet("255.255.255.255", 0, false), artnet("127.0.0.1", 2, false)]).is_ok());
+    }
+
+    #[test]
+    fn detects_size_of_matrix() {
+        let (host, _requests) = serve(vec![(200, info_json(512, Some((32, 16)), false))]);
+        let mut target = target(&host, None);
+
+        Settings::detect_size(&mut target).unwrap();
+
+        assert_eq!(target.size, Some((32, 16)));
+    }
+
+    #[test]
+    fn detects_size_of_strip() {
+        let (host, _requests) = serve(vec![(200, info_json(60, None, true))]);
+        let mut target = target(&host, None);
+
+        Settings::detect_size(&mut target).unwrap();
+
+        assert_eq!(target.size, Some((60, 0)));
+    }
+
+    #[test]
+    fn keeps_configured_size_without_querying() {
+        let (host, requests) = serve(vec![(200, info_json(512, Some((32, 16)), false))]);
+        let mut target = target(&host, Some((16, 16)));
+
+        Settings::detect_size(&mut target).unwrap();
+
+        assert_eq!(target.size, Some((16, 16)));
+        assert!(requests.try_recv().is_err());
+    }
+
+    #[test]
+    fn detects_sizes_of_all_targets() {
+        let (matrix, _matrix_requests) = serve(vec![(200, info_json(512, Some((32, 16)), false))]);
+        let (strip, _strip_requests) = serve(vec![(200, info_json(60, None, false))]);
+        let mut targets = vec![target(&matrix, None), target(&strip, None), target("unused", Some((8, 8)))];
+
+        Settings::detect_sizes(&mut targets).unwrap();
+
+        assert_eq!(targets.iter().map(|target| target.size).collect::<Vec<_>>(), [Some((32, 16)), Some((60, 0)), Some((8, 8))]);
+    }
+
+    #[test]
+    fn rejects_detected_size_over_255() {
+        let (host, _requests) = serve(vec![(200, info_json(300, None, false))]);
+        let mut target = target(&host, None);
+
+        assert!(Settings::detect_size(&mut target).is_err());
+    }
+
+    #[test]
+    fn unreachable_device_requires_size() {
+        // nothing listens on the port once the listener is dropped
+        let host = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().to_string();
+
+        assert!(Settings::detect_sizes(&mut [target(&host, None)]).is_err());
+        assert!(Settings::detect_sizes(&mut [target(&host, Some((16, 16)))]).is_ok());
+    }
+}
@@ -0,0 +1,32 @@
+/////////////////////////////////////////
+// Color Utility Methods
+////////////////////////////////////////
+
+///
+/// Approximate color of a black body at the given temperature,
+/// see https://tannerhelland.com/2012/09/18/convert-temperature-rgb-algorithm-code.html
+///
+/// Returns:
+///     red, green and blue, scaled so the brightest is 1.0
+pub fn temperature_to_rgb(temperature: u16) -> [f64; 3] {
+    let temperature = temperature as f64 / 100.0;
+
+    let red = match temperature <= 66.0 {
+        true => 255.0,
+        false => 329.698727446 * (temperature - 60.0).powf(-0.1332047592),
+    };
+    let green = match temperature <= 66.0 {
+        true => 99.4708025861 * temperature.ln() - 161.1195681661,
+        false => 288.1221695283 * (temperature - 60.0).powf(-0.0755148492),
+    };
+    let blue = match temperature {
+        temperature if temperature >= 66.0 => 255.0,
+        temperature if temperature <= 19.0 => 0.0,
+        temperature => 138.5177312231 * (temperature - 10.0).ln() - 305.0447927307,
+    };
+
+    let rgb = [red, green, blue].map(|value| value.clamp(0.0, 255.0));
+    let max = rgb.iter().copied().fold(f64::EPSILON, f64::max);
+
+    rgb.map(|value| value / max)
+}
@@ -1,17 +1,75 @@
 use std::error::Error;
 use std::path::Path;
-use image::{DynamicImage, ImageFormat};
+use image::{DynamicImage, GenericImageView, ImageFormat, Rgb, RgbImage};
+use image::imageops::FilterType;
 use std::{fs, thread};
 use log::{debug, log, trace};
+use crate::settings::FitMode;
 
-pub fn get_image_pixels(url: Option<String>, width: &u32, height: &u32) -> Result<Vec<u8>, Box<dyn Error>> {
-    let img = match url {
-        Some(url) => get_image_sized(url.as_str(), width, height)?,
+// the image is downsampled to at most this size (preserving its aspect ratio) to find its salient region
+static SALIENCY_SIZE: u32 = 64;
+
+///
+/// Renders the image to `width` x `height` pixels, fitted according to `fit`.
+///
+/// `image` - the image, or None for a black image
+/// `background` - color around the image in the `letterbox` fit mode
+///
+/// Returns:
+///     the pixels in row-major RGB
+pub fn get_image_pixels(image: Option<&DynamicImage>, width: &u32, height: &u32, fit: &FitMode, background: &[u8; 3]) -> Vec<u8> {
+    to_pixels(&get_image_fitted(image, width, height, fit, background))
+}
+
+/// Same as `get_image_pixels`, but returns the image itself, e.g. to take regions of it
+pub fn get_image_fitted(image: Option<&DynamicImage>, width: &u32, height: &u32, fit: &FitMode, background: &[u8; 3]) -> DynamicImage {
+    match image {
+        Some(image) => get_image_sized(image, width, height, fit, background),
         None => DynamicImage::new_rgb8(*width, *height),
-    };
+    }
+}
+
+///
+/// Renders a region of the image to `width` x `height` pixels, scaling it if its size differs.
+///
+/// `region` - x, y, width and height of the region, within the image
+pub fn get_region_pixels(image: &DynamicImage, region: (u32, u32, u32, u32), width: &u32, height: &u32) -> Vec<u8> {
+    let (x, y, region_width, region_height) = region;
+    let cropped = image.crop_imm(x, y, region_width, region_height);
+
+    match (region_width, region_height) == (*width, *height) {
+        true => to_pixels(&cropped),
+        false => to_pixels(&cropped.resize_exact(*width, *height, FilterType::Lanczos3)),
+    }
+}
 
-    let pixels = img.to_rgb8().pixels().map(|p| vec![p[0], p[1], p[2]]).flatten().collect();
-    Ok(pixels)
+///
+/// Samples `length` pixels evenly spaced along the edge of a region of the image,
+/// clockwise from its top left corner, e.g. for a strip wrapped around a screen.
+///
+/// `region` - x, y, width and height of the region, within the image
+pub fn get_perimeter_pixels(image: &DynamicImage, region: (u32, u32, u32, u32), length: usize) -> Vec<u8> {
+    let (x, y, width, height) = region;
+    let (right, bottom) = (x + width - 1, y + height - 1);
+
+    // each pixel on the edge once, in order: top, right, bottom (backwards) and left (upwards) side
+    let mut edge: Vec<(u32, u32)> = (x..=right).map(|edge_x| (edge_x, y)).collect();
+    edge.extend((y + 1..=bottom).map(|edge_y| (right, edge_y)));
+    if bottom > y {
+        edge.extend((x..right).rev().map(|edge_x| (edge_x, bottom)));
+    }
+    if right > x {
+        edge.extend((y + 1..bottom).rev().map(|edge_y| (x, edge_y)));
+    }
+
+    (0..length)
+        .map(|i| edge[i * edge.len() / length.max(1)])
+        .flat_map(|(edge_x, edge_y)| image.get_pixel(edge_x, edge_y).0[..3].to_vec())
+        .collect()
+}
+
+fn to_pixels(image: &DynamicImage) -> Vec<u8> {
+    image.to_rgb8().pixels().flat_map(|p| vec![p[0], p[1], p[2]]).collect()
 }
 
 pub fn precache_image(url: &str) -> Result<(), Box<dyn Error>> {
@@ -26,13 +84,101 @@ pub fn precache_image(url: &str) -> Result<(), Box<dyn Error>> {
     }
 }
 
-fn get_image_sized(url: &str, width: &u32, height: &u32) -> Result<DynamicImage, Box<dyn Error>> {
-    let img = get_image_raw(url)?;
-    let img = img.resize_exact(*height, *width, image::imageops::FilterType::Lanczos3);
-    Ok(img)
+fn get_image_sized(img: &DynamicImage, width: &u32, height: &u32, fit: &FitMode, background: &[u8; 3]) -> DynamicImage {
+    let (width, height) = (*width, *height);
+
+    match fit {
+        FitMode::Stretch => img.resize_exact(width, height, FilterType::Lanczos3),
+        FitMode::Letterbox => {
+            let resized = img.resize(width, height, FilterType::Lanczos3);
+            let mut canvas = RgbImage::from_pixel(width, height, Rgb(*background));
+
+            let x = (width - resized.width()) / 2;
+            let y = (height - resized.height()) / 2;
+            image::imageops::overlay(&mut canvas, &resized.to_rgb8(), x as i64, y as i64);
+
+            DynamicImage::ImageRgb8(canvas)
+        },
+        FitMode::Crop => img.resize_to_fill(width, height, FilterType::Lanczos3),
+        FitMode::SmartCrop => {
+            let (x, y, crop_width, crop_height) = get_salient_crop(img, width, height);
+            trace!("Smart crop of {} x {} image: {} x {} at ({}, {})", img.width(), img.height(), crop_width, crop_height, x, y);
+
+            img.crop_imm(x, y, crop_width, crop_height).resize_exact(width, height, FilterType::Lanczos3)
+        },
+    }
+}
+
+///
+/// Finds the crop of `img` with the aspect ratio of `width` x `height` covering the most salient region,
+/// i.e. the most detailed and colorful part, which is usually the subject of a cover.
+///
+/// Returns:
+///     x, y, width and height of the crop
+fn get_salient_crop(img: &DynamicImage, width: u32, height: u32) -> (u32, u32, u32, u32) {
+    let (image_width, image_height) = img.dimensions();
+
+    // only one dimension is cropped, so the saliency only needs to be known along it
+    let horizontal = image_width as u64 * height as u64 > image_height as u64 * width as u64;
+    let (crop_width, crop_height) = match horizontal {
+        true => (((image_height as u64 * width as u64) / height as u64).max(1) as u32, image_height),
+        false => (image_width, ((image_width as u64 * height as u64) / width as u64).max(1) as u32),
+    };
+
+    let thumbnail = img.thumbnail(SALIENCY_SIZE, SALIENCY_SIZE).to_rgb8();
+    let saliency = get_saliency(&thumbnail);
+    let (thumbnail_width, thumbnail_height) = thumbnail.dimensions();
+
+    // saliency summed along the cropped dimension
+    let profile: Vec<f64> = match horizontal {
+        true => (0..thumbnail_width).map(|x| (0..thumbnail_height).map(|y| saliency[(y * thumbnail_width + x) as usize]).sum()).collect(),
+        false => (0..thumbnail_height).map(|y| (0..thumbnail_width).map(|x| saliency[(y * thumbnail_width + x) as usize]).sum()).collect(),
+    };
+
+    let (image_length, crop_length) = match horizontal {
+        true => (image_width, crop_width),
+        false => (image_height, crop_height),
+    };
+    let window = ((crop_length as f64 / image_length as f64 * profile.len() as f64).round() as usize).clamp(1, profile.len().max(1));
+
+    // window of the profile with the highest saliency, ties go to the one closest to the center
+    let center = (profile.len() - window) as f64 / 2.0;
+    let best = (0..=profile.len().saturating_sub(window))
+        .map(|start| (start, profile[start..start + window].iter().sum::<f64>()))
+        .max_by(|a, b| a.1.total_cmp(&b.1).then((b.0 as f64 - center).abs().total_cmp(&(a.0 as f64 - center).abs())))
+        .map_or(0, |(start, _)| start);
+
+    let offset = ((best as f64 / profile.len().max(1) as f64 * image_length as f64).round() as u32).min(image_length - crop_length);
+
+    match horizontal {
+        true => (offset, 0, crop_width, crop_height),
+        false => (0, offset, crop_width, crop_height),
+    }
+}
+
+///
+/// Saliency of each pixel of the image: its color distance from the average color of the image,
+/// plus the color difference to its right and bottom neighbors (i.e. edges).
+fn get_saliency(image: &RgbImage) -> Vec<f64> {
+    let (width, height) = image.dimensions();
+    let num_pixels = (width * height).max(1) as f64;
+
+    let mean = [0, 1, 2].map(|channel| image.pixels().map(|pixel| pixel[channel] as f64).sum::<f64>() / num_pixels);
+    let distance = |a: &Rgb<u8>, b: [f64; 3]| -> f64 {
+        (0..3).map(|channel| (a[channel] as f64 - b[channel]).powi(2)).sum::<f64>().sqrt()
+    };
+    let as_f64 = |pixel: &Rgb<u8>| pixel.0.map(|value| value as f64);
+
+    image.enumerate_pixels().map(|(x, y, pixel)| {
+        let right = image.get_pixel((x + 1).min(width - 1), y);
+        let bottom = image.get_pixel(x, (y + 1).min(height - 1));
+
+        distance(pixel, mean) + distance(pixel, as_f64(right)) + distance(pixel, as_f64(bottom))
+    }).collect()
 }
 
-fn get_image_raw(url: &str) -> Result<DynamicImage, Box<dyn Error>> {
+/// Loads the image at `url`, from the cache if possible
+pub fn get_image_raw(url: &str) -> Result<DynamicImage, Box<dyn Error>> {
     let cache_path = get_cache_path(url, true);
 
     if Path::new(&cache_path).exists() {
@@ -1,3 +1,4 @@
+use std::net::Ipv4Addr;
 use dns_lookup::lookup_host;
 
 pub fn resolve_ip(host: &str) -> Result<String, &'static str> {
@@ -10,4 +11,12 @@ pub fn resolve_ip(host: &str) -> Result<String, &'static str> {
             return Err("Failed to resolve IP");
         }
     }
-}
\ No newline at end of file
+}
+
+///
+/// Whether the IP is the limited broadcast address (255.255.255.255).
+///
+/// Subnet broadcast addresses can't be told apart from hosts without the netmask (e.g. x.x.x.255 is a host on a /23 network).
+pub fn is_broadcast(ip: &str) -> bool {
+    ip.parse::<Ipv4Addr>().is_ok_and(|ip| ip.is_broadcast())
+}
@@ -0,0 +1,216 @@
+/////////////////////////////////////////
+// Cover Palette Utility Methods
+////////////////////////////////////////
+
+use color_thief::{ColorFormat, get_palette};
+use log::debug;
+use crate::settings::SETTINGS;
+use crate::lib::models::frame::VALUES_PER_PIXEL;
+
+// colors darker (max channel) or less saturated than these are not vivid enough to be shown on their own
+static MIN_BRIGHTNESS: u8 = 40;
+static MIN_SATURATION: f64 = 0.2;
+// the image is downsampled to at most this many pixels for k-means
+static KMEANS_MAX_SAMPLES: usize = 1024;
+static KMEANS_ITERATIONS: usize = 10;
+// extra clusters for k-means, as some of them are expected to be rejected as dull
+static KMEANS_EXTRA_CLUSTERS: usize = 4;
+// last resort, if the cover is near-black
+static FALLBACK_COLOR: [u8; 3] = [255, 255, 255];
+
+///
+/// Returns up to `size` vivid colors of `image` (row-major RGB), most dominant first.
+///
+/// Never fails, falling back in order to:
+///     1. the palette from color-thief, without near-black and near-grey colors
+///     2. k-means clusters of the downsampled image, without near-black and near-grey colors
+///     3. `[animation] fallback_palette` from the settings, if set
+///     4. the average color of the image, or white if the image is near-black
+pub fn get_cover_palette(image: &[u8], size: usize) -> Vec<[u8; 3]> {
+    cover_palette(image, size, SETTINGS.read().unwrap().animation.fallback_palette.as_deref())
+}
+
+/// `get_cover_palette` with the `fallback_palette` passed in instead of read from the settings
+fn cover_palette(image: &[u8], size: usize, fallback_palette: Option<&[[u8; 3]]>) -> Vec<[u8; 3]> {
+    let size = size.max(1);
+
+    let palette: Vec<[u8; 3]> = match image.len() >= VALUES_PER_PIXEL {
+        true => get_palette(image, ColorFormat::Rgb, 1, (size.min(u8::MAX as usize) as u8).max(2))
+            .map(|colors| colors.iter().map(|color| [color.r, color.g, color.b]).collect())
+            .unwrap_or_default(),
+        false => Vec::new(),
+    };
+
+    let vivid = vivid_colors(&palette, size);
+    if !vivid.is_empty() {
+        return vivid;
+    }
+
+    debug!("No vivid colors in palette {:?}, falling back to k-means", palette);
+    let vivid = vivid_colors(&kmeans(image, size + KMEANS_EXTRA_CLUSTERS), size);
+    if !vivid.is_empty() {
+        return vivid;
+    }
+
+    if let Some(fallback_palette) = fallback_palette.filter(|palette| !palette.is_empty()) {
+        debug!("No vivid colors in cover, falling back to configured palette");
+        return fallback_palette.iter().take(size).copied().collect();
+    }
+
+    debug!("No vivid colors in cover, falling back to its average color");
+    match average_color(image) {
+        Some(color) if color.iter().max().unwrap() >= &MIN_BRIGHTNESS => vec![color],
+        _ => vec![FALLBACK_COLOR],
+    }
+}
+
+/// Whether the color is bright and saturated enough to be shown on its own
+pub fn is_vivid(color: [u8; 3]) -> bool {
+    let max = *color.iter().max().unwrap();
+    let min = *color.iter().min().unwrap();
+
+    max >= MIN_BRIGHTNESS && (max - min) as f64 / max as f64 >= MIN_SATURATION
+}
+
+fn vivid_colors(colors: &[[u8; 3]], size: usize) -> Vec<[u8; 3]> {
+    colors.iter().filter(|color| is_vivid(**color)).take(size).copied().collect()
+}
+
+fn average_color(image: &[u8]) -> Option<[u8; 3]> {
+    let pixels = image.len() / VALUES_PER_PIXEL;
+
+    if pixels == 0 {
+        return None;
+    }
+
+    Some([0, 1, 2].map(|channel| {
+        (image.chunks_exact(VALUES_PER_PIXEL).map(|pixel| pixel[channel] as usize).sum::<usize>() / pixels) as u8
+    }))
+}
+
+///
+/// Clusters the colors of the downsampled image into (at most) `k` clusters.
+///
+/// Returns:
+///     the center of each cluster, largest cluster first
+fn kmeans(image: &[u8], k: usize) -> Vec<[u8; 3]> {
+    let pixels: Vec<[f64; 3]> = image.chunks_exact(VALUES_PER_PIXEL)
+        .step_by((image.len() / VALUES_PER_PIXEL / KMEANS_MAX_SAMPLES).max(1))
+        .map(|pixel| [pixel[0] as f64, pixel[1] as f64, pixel[2] as f64])
+        .collect();
+
+    if pixels.is_empty() {
+        return Vec::new();
+    }
+
+    // deterministic initialization, spread over the samples
+    let k = k.min(pixels.len());
+    let mut centers: Vec<[f64; 3]> = (0..k).map(|i| pixels[i * pixels.len() / k]).collect();
+    let mut counts = vec![0usize; k];
+
+    for _ in 0..KMEANS_ITERATIONS {
+        let mut sums = vec![[0.0; 3]; k];
+        counts = vec![0; k];
+
+        for pixel in pixels.iter() {
+            let nearest = (0..k)
+                .min_by(|a, b| distance(pixel, &centers[*a]).total_cmp(&distance(pixel, &centers[*b])))
+                .unwrap();
+
+            for channel in 0..3 {
+                sums[nearest][channel] += pixel[channel];
+            }
+            counts[nearest] += 1;
+        }
+
+        for i in 0..k {
+            if counts[i] > 0 {
+                centers[i] = sums[i].map(|sum| sum / counts[i] as f64);
+            }
+        }
+    }
+
+    let mut clusters: Vec<([f64; 3], usize)> = centers.into_iter().zip(counts).filter(|(_, count)| *count > 0).collect();
+    clusters.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+
+    clusters.iter().map(|(center, _)| center.map(|value| value.round() as u8)).collect()
+}
+
+fn distance(a: &[f64; 3], b: &[f64; 3]) -> f64 {
+    (0..3).map(|channel| (a[channel] - b[channel]).powi(2)).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static FALLBACK: [[u8; 3]; 2] = [[255, 120, 0], [0, 120, 255]];
+
+    /// Row-major RGB image of `pixels` pixels of a single color
+    fn filled(color: [u8; 3], pixels: usize) -> Vec<u8> {
+        color.repeat(pixels)
+    }
+
+    /// Asserts the palette only has colors close to `expected`, as color-thief quantizes colors
+    fn assert_all_near(palette: &[[u8; 3]], expected: [u8; 3]) {
+        assert!(!palette.is_empty(), "got an empty palette");
+        assert!(palette.iter().all(|color| color.iter().zip(expected).all(|(a, b)| a.abs_diff(b) <= 8)), "expected {:?}, got {:?}", expected, palette);
+    }
+
+    #[test]
+    fn black_cover_falls_back_to_configured_palette_then_white() {
+        let black = filled([0, 0, 0], 16 * 16);
+
+        assert_eq!(cover_palette(&black, 4, Some(&FALLBACK)), FALLBACK);
+        assert_eq!(cover_palette(&black, 1, Some(&FALLBACK)), [FALLBACK[0]]);
+        assert_eq!(cover_palette(&black, 4, Some(&[])), [FALLBACK_COLOR]);
+        assert_eq!(cover_palette(&black, 4, None), [FALLBACK_COLOR]);
+    }
+
+    #[test]
+    fn grey_cover_falls_back_to_configured_palette_then_average_color() {
+        let grey = filled([128, 128, 128], 16 * 16);
+        assert_eq!(cover_palette(&grey, 4, Some(&FALLBACK)), FALLBACK);
+        assert_eq!(cover_palette(&grey, 4, None), [[128, 128, 128]]);
+
+        // too dark to be shown, even as the average
+        assert_eq!(cover_palette(&filled([20, 20, 20], 16 * 16), 4, None), [FALLBACK_COLOR]);
+    }
+
+    #[test]
+    fn monochrome_cover_has_its_color() {
+        let red = filled([200, 30, 30], 16 * 16);
+        let palette = cover_palette(&red, 4, Some(&FALLBACK));
+
+        assert!(palette.len() <= 4);
+        assert_all_near(&palette, [200, 30, 30]);
+    }
+
+    #[test]
+    fn single_pixel_cover() {
+        let palette = cover_palette(&[200, 30, 30], 4, Some(&FALLBACK));
+        assert_eq!(palette.len(), 1);
+        assert_all_near(&palette, [200, 30, 30]);
+        assert_eq!(cover_palette(&[0, 0, 0], 4, Some(&FALLBACK)), FALLBACK);
+        assert_eq!(cover_palette(&[128, 128, 128], 4, None), [[128, 128, 128]]);
+    }
+
+    #[test]
+    fn empty_cover_falls_back() {
+        assert_eq!(cover_palette(&[], 4, Some(&FALLBACK)), FALLBACK);
+        assert_eq!(cover_palette(&[], 4, None), [FALLBACK_COLOR]);
+    }
+
+    #[test]
+    fn returns_vivid_colors_most_dominant_first() {
+        // 3/4 blue, 1/4 red, on a black border
+        let mut image = filled([0, 0, 200], 16 * 12);
+        image.extend(filled([200, 0, 0], 16 * 4));
+        image.extend(filled([0, 0, 0], 16 * 2));
+        let palette = cover_palette(&image, 4, Some(&FALLBACK));
+
+        assert!(palette.iter().all(|color| is_vivid(*color)), "got {:?}", palette);
+        assert!(palette[0][2] > 150 && palette[0][0] < 50, "got {:?}", palette);
+        assert!(palette.iter().any(|color| color[0] > 150 && color[2] < 50), "got {:?}", palette);
+    }
+}
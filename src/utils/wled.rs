@@ -0,0 +1,207 @@
+/////////////////////////////////////////
+// WLED JSON API Utility Methods
+////////////////////////////////////////
+
+use std::time::Duration;
+use reqwest::blocking::Client;
+use serde::de::DeserializeOwned;
+use serde_derive::Deserialize;
+use serde_json::Value;
+
+static WLED_REQUEST_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Device info from WLED's `/json/info`, only the fields we use
+#[derive(Debug, Deserialize, Clone)]
+pub struct WledInfo {
+    pub ver: String,
+    pub name: String,
+    pub leds: WledLedInfo,
+    // whether the device is currently receiving realtime data
+    #[serde(default)]
+    pub live: bool,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct WledLedInfo {
+    pub count: u16,
+    // only present if the device is configured as a 2-D matrix
+    pub matrix: Option<WledMatrixInfo>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct WledMatrixInfo {
+    pub w: u16,
+    pub h: u16,
+}
+
+impl WledInfo {
+    /// Size of the device in the format of `Target.size`, i.e. (LED count, 0) for 1-D devices
+    pub fn size(&self) -> (u16, u16) {
+        match &self.leds.matrix {
+            Some(matrix) if matrix.w > 0 && matrix.h > 0 => (matrix.w, matrix.h),
+            _ => (self.leds.count, 0),
+        }
+    }
+}
+
+///
+/// Gets the device info of the WLED device at `host`
+///
+/// `host` - hostname or IP of the device, optionally with port
+pub fn get_info(host: &str) -> Result<WledInfo, reqwest::Error> {
+    get_json(host, "info")
+}
+
+///
+/// Gets the current state (power, brightness, preset, segments, etc.) of the WLED device at `host`
+pub fn get_state(host: &str) -> Result<Value, reqwest::Error> {
+    get_json(host, "state")
+}
+
+///
+/// Applies `state` to the WLED device at `host`; only the fields present in `state` are changed
+pub fn set_state(host: &str, state: &Value) -> Result<(), reqwest::Error> {
+    get_client()?
+        .post(format!("http://{}/json/state", host))
+        .json(state)
+        .send()?
+        .error_for_status()?;
+
+    Ok(())
+}
+
+fn get_client() -> Result<Client, reqwest::Error> {
+    Client::builder().timeout(WLED_REQUEST_TIMEOUT).build()
+}
+
+fn get_json<T: DeserializeOwned>(host: &str, path: &str) -> Result<T, reqwest::Error> {
+    get_client()?
+        .get(format!("http://{}/json/{}", host, path))
+        .send()?
+        .error_for_status()?
+        .json::<T>()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+    use std::thread;
+    use serde_json::json;
+    use super::*;
+
+    /// Request received by the test server: request line (e.g. `GET /json/info HTTP/1.1`) and body
+    pub(crate) type Request = (String, String);
+
+    ///
+    /// Serves one canned response per request on a local port, mocking the WLED JSON API
+    ///
+    /// `responses` - status code and JSON body of each response, in order
+    ///
+    /// Returns:
+    ///     the host of the server, and the requests it received
+    pub(crate) fn serve(responses: Vec<(u16, String)>) -> (String, mpsc::Receiver<Request>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let host = listener.local_addr().unwrap().to_string();
+        let (tx, rx) = mpsc::channel();
+
+        thread::spawn(move || {
+            for (status, body) in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+
+                let mut content_length = 0;
+                loop {
+                    let mut header = String::new();
+                    reader.read_line(&mut header).unwrap();
+                    if header.trim().is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = header.split_once(':') {
+                        if name.eq_ignore_ascii_case("content-length") {
+                            content_length = value.trim().parse().unwrap();
+                        }
+                    }
+                }
+
+                let mut request_body = vec![0; content_length];
+                reader.read_exact(&mut request_body).unwrap();
+                tx.send((request_line.trim().to_string(), String::from_utf8(request_body).unwrap())).unwrap();
+
+                write!(stream, "HTTP/1.1 {} Test\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", status, body.len(), body).unwrap();
+            }
+        });
+
+        (host, rx)
+    }
+
+    /// Body of `/json/info` of a device with `count` LEDs, and a `w` x `h` matrix if given
+    pub(crate) fn info_json(count: u16, matrix: Option<(u16, u16)>, live: bool) -> String {
+        let mut leds = json!({ "count": count });
+        if let Some((w, h)) = matrix {
+            leds["matrix"] = json!({ "w": w, "h": h });
+        }
+        json!({ "ver": "0.14.0", "name": "WLED", "leds": leds, "live": live }).to_string()
+    }
+
+    #[test]
+    fn gets_info_of_matrix() {
+        let (host, requests) = serve(vec![(200, info_json(1024, Some((32, 32)), true))]);
+
+        let info = get_info(&host).unwrap();
+
+        assert_eq!(requests.recv().unwrap().0, "GET /json/info HTTP/1.1");
+        assert_eq!(info.ver, "0.14.0");
+        assert_eq!(info.size(), (32, 32));
+        assert!(info.live);
+    }
+
+    #[test]
+    fn gets_info_of_strip() {
+        let (host, _requests) = serve(vec![(200, json!({ "ver": "0.14.0", "name": "Strip", "leds": { "count": 60 } }).to_string())]);
+
+        let info = get_info(&host).unwrap();
+
+        assert_eq!(info.size(), (60, 0));
+        assert!(!info.live);
+    }
+
+    #[test]
+    fn get_info_fails_on_error_status() {
+        let (host, _requests) = serve(vec![(500, "{}".to_string())]);
+
+        assert!(get_info(&host).is_err());
+    }
+
+    #[test]
+    fn gets_state() {
+        let state = json!({ "on": true, "bri": 128, "ps": 3 });
+        let (host, requests) = serve(vec![(200, state.to_string())]);
+
+        assert_eq!(get_state(&host).unwrap(), state);
+        assert_eq!(requests.recv().unwrap().0, "GET /json/state HTTP/1.1");
+    }
+
+    #[test]
+    fn sets_state() {
+        let state = json!({ "on": false });
+        let (host, requests) = serve(vec![(200, json!({ "success": true }).to_string())]);
+
+        set_state(&host, &state).unwrap();
+
+        let (request_line, body) = requests.recv().unwrap();
+        assert_eq!(request_line, "POST /json/state HTTP/1.1");
+        assert_eq!(serde_json::from_str::<Value>(&body).unwrap(), state);
+    }
+
+    #[test]
+    fn set_state_fails_on_error_status() {
+        let (host, _requests) = serve(vec![(400, "{}".to_string())]);
+
+        assert!(set_state(&host, &json!({ "on": true })).is_err());
+    }
+}